@@ -4,18 +4,74 @@ mod builders {
     // ------
     // mod car;
     // mod car_manuel;
+    use std::fmt;
+
     use crate::components::{CarType, Engine, GpsNavigator, Transmission};
     pub use car::CarBuilder;
     pub use car_manual::CarManualBuilder;
 
-    pub trait Builder {
+    pub trait Builder: Sized {
         type OutputType;
         fn set_car_type(&mut self, car_type: CarType);
         fn set_seats(&mut self, seats: u16);
         fn set_engine(&mut self, engine: Engine);
         fn set_transmission(&mut self, transmission: Transmission);
         fn set_gsp_navigator(&mut self, gps_navigator: GpsNavigator);
-        fn build(self) -> Self::OutputType;
+        fn try_build(self) -> Result<Self::OutputType, BuildError>;
+
+        /// Builds the product, panicking if it is not configured properly.
+        fn build(self) -> Self::OutputType {
+            self.try_build().unwrap_or_else(|err| panic!("{}", err))
+        }
+    }
+
+    /// Reasons for a builder to refuse building a product.
+    #[derive(Debug, PartialEq)]
+    pub enum BuildError {
+        MissingPart(&'static str),
+        NoSeats,
+        InvalidEngineVolume(f64),
+        InvalidMileage(f64),
+        InvalidFuel { fuel: f64, capacity: f64 },
+    }
+
+    impl fmt::Display for BuildError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                BuildError::MissingPart(part) => write!(f, "Please, set {}", part),
+                BuildError::NoSeats => write!(f, "A car needs at least one seat"),
+                BuildError::InvalidEngineVolume(volume) => {
+                    write!(f, "Engine volume of {} is not positive", volume)
+                }
+                BuildError::InvalidMileage(mileage) => {
+                    write!(f, "Engine mileage of {} is not valid", mileage)
+                }
+                BuildError::InvalidFuel { fuel, capacity } => write!(
+                    f,
+                    "Fuel of {} does not fit into a tank of {}",
+                    fuel, capacity
+                ),
+            }
+        }
+    }
+
+    impl std::error::Error for BuildError {}
+
+    /// Checks the parts that every car and its manual have in common.
+    fn validate(seats: u16, engine: &Engine) -> Result<(), BuildError> {
+        if seats == 0 {
+            return Err(BuildError::NoSeats);
+        }
+
+        if !(engine.volume() > 0.0 && engine.volume().is_finite()) {
+            return Err(BuildError::InvalidEngineVolume(engine.volume()));
+        }
+
+        if !(engine.mileage() >= 0.0 && engine.mileage().is_finite()) {
+            return Err(BuildError::InvalidMileage(engine.mileage()));
+        }
+
+        Ok(())
     }
 
     mod car {
@@ -24,7 +80,7 @@ mod builders {
             components::{CarType, Engine, GpsNavigator, Transmission},
         };
 
-        use super::Builder;
+        use super::{validate, BuildError, Builder};
 
         pub const DEFAULT_FUEL: f64 = 5.0;
 
@@ -32,11 +88,19 @@ mod builders {
         pub struct CarBuilder {
             car_type: Option<CarType>,
             engine: Option<Engine>,
+            fuel: Option<f64>,
             gps_navigator: Option<GpsNavigator>,
             seats: Option<u16>,
             transmission: Option<Transmission>,
         }
 
+        impl CarBuilder {
+            /// Fills the tank with a given amount of fuel instead of the default.
+            pub fn set_fuel(&mut self, fuel: f64) {
+                self.fuel = Some(fuel);
+            }
+        }
+
         impl Builder for CarBuilder {
             type OutputType = Car;
 
@@ -60,15 +124,34 @@ mod builders {
                 self.transmission = Some(transmission);
             }
 
-            fn build(self) -> Car {
-                Car::new(
-                    self.car_type.expect("Please, set a car type"),
-                    self.seats.expect("Please, set a number of seats"),
-                    self.engine.expect("Please, set an engine configuration"),
-                    self.transmission.expect("Please, set up transmission"),
+            fn try_build(self) -> Result<Car, BuildError> {
+                let car_type = self.car_type.ok_or(BuildError::MissingPart("a car type"))?;
+                let seats = self
+                    .seats
+                    .ok_or(BuildError::MissingPart("a number of seats"))?;
+                let engine = self
+                    .engine
+                    .ok_or(BuildError::MissingPart("an engine configuration"))?;
+                let transmission = self
+                    .transmission
+                    .ok_or(BuildError::MissingPart("up transmission"))?;
+
+                validate(seats, &engine)?;
+
+                let fuel = self.fuel.unwrap_or(DEFAULT_FUEL);
+                let capacity = car_type.tank_capacity();
+                if !(0.0..=capacity).contains(&fuel) {
+                    return Err(BuildError::InvalidFuel { fuel, capacity });
+                }
+
+                Ok(Car::new(
+                    car_type,
+                    seats,
+                    engine,
+                    transmission,
                     self.gps_navigator,
-                    DEFAULT_FUEL,
-                )
+                    fuel,
+                ))
             }
         }
     }
@@ -79,7 +162,7 @@ mod builders {
             components::{CarType, Engine, GpsNavigator, Transmission},
         };
 
-        use super::Builder;
+        use super::{validate, BuildError, Builder};
 
         #[derive(Default)]
         pub struct CarManualBuilder {
@@ -114,14 +197,27 @@ mod builders {
                 self.transmission = Some(transmission);
             }
 
-            fn build(self) -> Manual {
-                Manual::new(
-                    self.car_type.expect("Please, set a car type"),
-                    self.seats.expect("Please, set a number of seats"),
-                    self.engine.expect("Please, set an engine configuration"),
-                    self.transmission.expect("Please, set up transmission"),
+            fn try_build(self) -> Result<Manual, BuildError> {
+                let car_type = self.car_type.ok_or(BuildError::MissingPart("a car type"))?;
+                let seats = self
+                    .seats
+                    .ok_or(BuildError::MissingPart("a number of seats"))?;
+                let engine = self
+                    .engine
+                    .ok_or(BuildError::MissingPart("an engine configuration"))?;
+                let transmission = self
+                    .transmission
+                    .ok_or(BuildError::MissingPart("up transmission"))?;
+
+                validate(seats, &engine)?;
+
+                Ok(Manual::new(
+                    car_type,
+                    seats,
+                    engine,
+                    transmission,
                     self.gps_navigator,
-                )
+                ))
             }
         }
     }
@@ -133,12 +229,30 @@ mod cars {
     // ------
     // mod car;
     // mod manuor
+    // mod trip;
     pub use car::Car;
     pub use manual::Manual;
+    pub use trip::TripError;
 
     mod car {
-        use crate::components::{CarType, Engine, GpsNavigator, Transmission};
+        use serde::{Deserialize, Serialize};
+
+        use super::trip::{Trip, TripError, TripLog};
+        use crate::{
+            builders::{BuildError, Builder, CarBuilder},
+            components::{CarType, Engine, GpsNavigator, Transmission},
+            navigation::Route,
+        };
+
+        /// Fuel burnt per 100 km by an engine of zero volume, in litres.
+        const BASE_CONSUMPTION: f64 = 4.0;
+        /// Extra fuel burnt per 100 km for each litre of engine volume.
+        const CONSUMPTION_PER_LITRE: f64 = 2.5;
 
+        /// A car is deserialized through `CarBuilder`, so a stored car passes
+        /// the same checks as a freshly built one.
+        #[derive(Serialize, Deserialize)]
+        #[serde(try_from = "CarRecord")]
         pub struct Car {
             car_type: CarType,
             seats: u16,
@@ -146,6 +260,39 @@ mod cars {
             transmission: Transmission,
             gps_navigator: Option<GpsNavigator>,
             fuel: f64,
+            trip_log: TripLog,
+        }
+
+        /// A serialized car before validation.
+        #[derive(Deserialize)]
+        struct CarRecord {
+            car_type: CarType,
+            seats: u16,
+            engine: Engine,
+            transmission: Transmission,
+            gps_navigator: Option<GpsNavigator>,
+            fuel: f64,
+            trip_log: TripLog,
+        }
+
+        impl TryFrom<CarRecord> for Car {
+            type Error = BuildError;
+
+            fn try_from(record: CarRecord) -> Result<Self, Self::Error> {
+                let mut builder = CarBuilder::default();
+                builder.set_car_type(record.car_type);
+                builder.set_seats(record.seats);
+                builder.set_engine(record.engine);
+                builder.set_transmission(record.transmission);
+                if let Some(gps_navigator) = record.gps_navigator {
+                    builder.set_gsp_navigator(gps_navigator);
+                }
+                builder.set_fuel(record.fuel);
+
+                let mut car = builder.try_build()?;
+                car.trip_log = record.trip_log;
+                Ok(car)
+            }
         }
 
         impl Car {
@@ -164,6 +311,7 @@ mod cars {
                     transmission,
                     gps_navigator,
                     fuel,
+                    trip_log: TripLog::default(),
                 }
             }
 
@@ -174,10 +322,6 @@ mod cars {
                 self.fuel
             }
 
-            pub fn set_fuel(&mut self, fuel: f64) {
-                self.fuel = fuel;
-            }
-
             pub fn seats(&self) -> u16 {
                 self.seats
             }
@@ -193,12 +337,205 @@ mod cars {
             pub fn gps_navigator(&self) -> &Option<GpsNavigator> {
                 &self.gps_navigator
             }
+
+            pub fn trip_log(&self) -> &TripLog {
+                &self.trip_log
+            }
+
+            /// Total distance driven, as counted by the engine.
+            pub fn odometer(&self) -> f64 {
+                self.engine.mileage()
+            }
+
+            /// Fuel consumption in litres per 100 km: a bigger engine burns
+            /// more, and a heavier or sportier body burns more still.
+            pub fn consumption(&self) -> f64 {
+                (BASE_CONSUMPTION + CONSUMPTION_PER_LITRE * self.engine.volume())
+                    * self.car_type.consumption_factor()
+            }
+
+            /// How far the car can go on the fuel left in the tank.
+            pub fn range(&self) -> f64 {
+                self.fuel / self.consumption() * 100.0
+            }
+
+            pub fn start(&mut self) {
+                self.engine.on();
+            }
+
+            pub fn stop(&mut self) {
+                self.engine.off();
+            }
+
+            /// Tops the tank up, but never above its capacity.
+            ///
+            /// Returns the amount of fuel actually added.
+            pub fn refuel(&mut self, fuel: f64) -> f64 {
+                let capacity = self.car_type.tank_capacity();
+                let added = fuel.max(0.0).min(capacity - self.fuel).max(0.0);
+                self.fuel += added;
+                added
+            }
+
+            /// Drives the car for a given distance, burning fuel and recording
+            /// the trip in the log.
+            ///
+            /// Nothing changes if the trip cannot be made.
+            pub fn drive(&mut self, distance: f64) -> Result<&Trip, TripError> {
+                if !distance.is_finite() || distance <= 0.0 {
+                    return Err(TripError::InvalidDistance(distance));
+                }
+
+                if !self.engine.started() {
+                    return Err(TripError::EngineOff);
+                }
+
+                let range = self.range();
+                if distance > range {
+                    return Err(TripError::OutOfRange { distance, range });
+                }
+
+                let fuel_used = distance * self.consumption() / 100.0;
+                let odometer_start = self.odometer();
+
+                self.engine.go(distance);
+                self.fuel -= fuel_used;
+
+                Ok(self.trip_log.record(Trip {
+                    distance,
+                    fuel_used,
+                    fuel_left: self.fuel,
+                    odometer_start,
+                    odometer_end: self.odometer(),
+                }))
+            }
+
+            /// Drives a route planned by a GPS navigator.
+            ///
+            /// A route from a place to itself has no legs, so the car is
+            /// already there: the trip is empty and is not logged.
+            pub fn drive_route(&mut self, route: &Route) -> Result<Trip, TripError> {
+                if route.legs().is_empty() {
+                    return Ok(Trip {
+                        distance: 0.0,
+                        fuel_used: 0.0,
+                        fuel_left: self.fuel,
+                        odometer_start: self.odometer(),
+                        odometer_end: self.odometer(),
+                    });
+                }
+
+                self.drive(route.distance()).cloned()
+            }
+        }
+    }
+
+    mod trip {
+        use std::{fmt, io};
+
+        use serde::{Deserialize, Serialize};
+
+        /// A single completed trip.
+        #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+        pub struct Trip {
+            pub distance: f64,
+            pub fuel_used: f64,
+            pub fuel_left: f64,
+            pub odometer_start: f64,
+            pub odometer_end: f64,
+        }
+
+        /// Reasons for a car to refuse a trip.
+        #[derive(Debug, PartialEq)]
+        pub enum TripError {
+            EngineOff,
+            InvalidDistance(f64),
+            OutOfRange { distance: f64, range: f64 },
+        }
+
+        impl fmt::Display for TripError {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                match self {
+                    TripError::EngineOff => write!(f, "the engine must be started first"),
+                    TripError::InvalidDistance(distance) => {
+                        write!(f, "cannot drive a distance of {distance} km")
+                    }
+                    TripError::OutOfRange { distance, range } => write!(
+                        f,
+                        "a trip of {distance:.1} km is beyond the range of {range:.1} km"
+                    ),
+                }
+            }
+        }
+
+        impl std::error::Error for TripError {}
+
+        /// All trips made by a car, in order.
+        #[derive(Default, Serialize, Deserialize)]
+        #[serde(transparent)]
+        pub struct TripLog {
+            trips: Vec<Trip>,
+        }
+
+        impl TripLog {
+            pub(super) fn record(&mut self, trip: Trip) -> &Trip {
+                self.trips.push(trip);
+                self.trips.last().unwrap()
+            }
+
+            pub fn trips(&self) -> &[Trip] {
+                &self.trips
+            }
+
+            pub fn total_distance(&self) -> f64 {
+                self.trips
+                    .iter()
+                    .fold(0.0, |total, trip| total + trip.distance)
+            }
+
+            pub fn total_fuel_used(&self) -> f64 {
+                self.trips
+                    .iter()
+                    .fold(0.0, |total, trip| total + trip.fuel_used)
+            }
+
+            /// Writes the log as CSV, one trip per line.
+            pub fn export_csv(&self, mut writer: impl io::Write) -> io::Result<()> {
+                writeln!(
+                    writer,
+                    "trip,distance,fuel_used,fuel_left,odometer_start,odometer_end"
+                )?;
+
+                for (number, trip) in self.trips.iter().enumerate() {
+                    writeln!(
+                        writer,
+                        "{},{:.2},{:.3},{:.3},{:.2},{:.2}",
+                        number + 1,
+                        trip.distance,
+                        trip.fuel_used,
+                        trip.fuel_left,
+                        trip.odometer_start,
+                        trip.odometer_end
+                    )?;
+                }
+
+                Ok(())
+            }
         }
     }
 
     mod manual {
-        use crate::components::{CarType, Engine, GpsNavigator, Transmission};
+        use serde::{Deserialize, Serialize};
+
+        use crate::{
+            builders::{BuildError, Builder, CarManualBuilder},
+            components::{CarType, Engine, GpsNavigator, Transmission},
+            rendering::{Document, Format, Locale},
+        };
 
+        /// A manual is deserialized through `CarManualBuilder` to be validated.
+        #[derive(Serialize, Deserialize)]
+        #[serde(try_from = "ManualRecord")]
         pub struct Manual {
             car_type: CarType,
             seats: u16,
@@ -207,6 +544,33 @@ mod cars {
             gps_navigator: Option<GpsNavigator>,
         }
 
+        /// A serialized manual before validation.
+        #[derive(Deserialize)]
+        struct ManualRecord {
+            car_type: CarType,
+            seats: u16,
+            engine: Engine,
+            transmission: Transmission,
+            gps_navigator: Option<GpsNavigator>,
+        }
+
+        impl TryFrom<ManualRecord> for Manual {
+            type Error = BuildError;
+
+            fn try_from(record: ManualRecord) -> Result<Self, Self::Error> {
+                let mut builder = CarManualBuilder::default();
+                builder.set_car_type(record.car_type);
+                builder.set_seats(record.seats);
+                builder.set_engine(record.engine);
+                builder.set_transmission(record.transmission);
+                if let Some(gps_navigator) = record.gps_navigator {
+                    builder.set_gsp_navigator(gps_navigator);
+                }
+
+                builder.try_build()
+            }
+        }
+
         impl Manual {
             pub fn new(
                 car_type: CarType,
@@ -225,118 +589,2014 @@ mod cars {
             }
         }
 
+        impl Manual {
+            /// Translates the manual's content into a document to be rendered.
+            pub fn document(&self, locale: Locale) -> Document {
+                let catalog = locale.catalog();
+                let car_type = match self.car_type {
+                    CarType::CityCar => "car_type.city_car",
+                    CarType::SportsCar => "car_type.sports_car",
+                    CarType::Suv => "car_type.suv",
+                };
+                let transmission = match self.transmission {
+                    Transmission::SingleSpeed => "transmission.single_speed",
+                    Transmission::Manual => "transmission.manual",
+                    Transmission::Automatic => "transmission.automatic",
+                    Transmission::SemiAutomatic => "transmission.semi_automatic",
+                };
+                let gps_navigator = match &self.gps_navigator {
+                    Some(gps_navigator) => catalog.format(
+                        "gps_navigator.functional",
+                        &[("places", gps_navigator.map().places().len().to_string())],
+                    ),
+                    None => catalog.get("gps_navigator.unavailable").into(),
+                };
+
+                let entries = vec![
+                    (catalog.get("car_type"), catalog.get(car_type).into()),
+                    (catalog.get("seats"), self.seats.to_string()),
+                    (
+                        catalog.get("engine"),
+                        catalog.format(
+                            "engine.value",
+                            &[
+                                ("volume", self.engine.volume().to_string()),
+                                ("mileage", self.engine.mileage().to_string()),
+                            ],
+                        ),
+                    ),
+                    (
+                        catalog.get("transmission"),
+                        catalog.get(transmission).into(),
+                    ),
+                    (catalog.get("gps_navigator"), gps_navigator),
+                ];
+
+                Document {
+                    locale,
+                    title: catalog.get("title").into(),
+                    entries: entries
+                        .into_iter()
+                        .map(|(label, value)| (label.into(), value))
+                        .collect(),
+                }
+            }
+
+            pub fn render(&self, format: Format, locale: Locale) -> String {
+                self.document(locale).render(format)
+            }
+        }
+
         impl std::fmt::Display for Manual {
             fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-                writeln!(f, "Type of car: {:?}", self.car_type)?;
-                writeln!(f, "Count of seats: {}", self.seats)?;
-                writeln!(
-                    f,
-                    "Engine: volume - {}; mileage - {}",
-                    self.engine.volume(),
-                    self.engine.mileage()
-                )?;
-                writeln!(f, "Transmission: {:?}", self.transmission)?;
-                match self.gps_navigator {
-                    Some(_) => writeln!(f, "GPS Navigator: Functional")?,
-                    None => writeln!(f, "GPS Navigator: N/A")?,
-                };
-                Ok(())
+                f.write_str(&self.render(Format::PlainText, Locale::English))
             }
         }
     }
-}
 
-mod components {
-    #[derive(Copy, Clone, Debug)]
-    pub enum CarType {
-        CityCar,
-        SportsCar,
-        Suv,
-    }
+    #[cfg(test)]
+    mod tests {
+        use std::{fs, path::Path};
 
-    #[derive(Debug)]
-    pub enum Transmission {
-        SingleSpeed,
-        Manual,
-        Automatic,
-        SemiAutomatic,
-    }
+        use serde_json::{json, Value};
 
-    pub struct Engine {
-        volume: f64,
-        mileage: f64,
-        started: bool,
-    }
+        use super::{Car, Manual, TripError};
+        use crate::{
+            builders::{Builder, CarBuilder, CarManualBuilder},
+            components::{CarType, Engine, GpsNavigator, Transmission},
+            director::Director,
+            navigation::RouteKind,
+            rendering::{Format, Locale},
+        };
 
-    impl Engine {
-        pub fn new(volume: f64, mileage: f64) -> Self {
-            Self {
-                volume,
-                mileage,
-                started: false,
-            }
+        const CAR_TYPES: [CarType; 3] = [CarType::CityCar, CarType::SportsCar, CarType::Suv];
+
+        fn transmissions() -> [Transmission; 4] {
+            [
+                Transmission::SingleSpeed,
+                Transmission::Manual,
+                Transmission::Automatic,
+                Transmission::SemiAutomatic,
+            ]
         }
 
-        pub fn on(&mut self) {
-            self.started = true;
+        fn configure(builder: &mut impl Builder, car_type: CarType, transmission: Transmission) {
+            builder.set_car_type(car_type);
+            builder.set_seats(4);
+            builder.set_engine(Engine::new(1.6, 1200.0));
+            builder.set_transmission(transmission);
+            builder.set_gsp_navigator(GpsNavigator::new());
         }
 
-        pub fn off(&mut self) {
-            self.started = false;
+        /// Every kind of car, after a trip to fill its log.
+        fn cars() -> Vec<Car> {
+            let mut cars = vec![];
+            for car_type in CAR_TYPES {
+                for transmission in transmissions() {
+                    let mut builder = CarBuilder::default();
+                    configure(&mut builder, car_type, transmission);
+                    let mut car = builder.build();
+                    car.start();
+                    car.drive(12.5).unwrap();
+                    car.stop();
+                    cars.push(car);
+                }
+            }
+            cars
         }
 
-        pub fn started(&self) -> bool {
-            self.started
+        fn manuals() -> Vec<Manual> {
+            let mut manuals = vec![];
+            for car_type in CAR_TYPES {
+                for transmission in transmissions() {
+                    let mut builder = CarManualBuilder::default();
+                    configure(&mut builder, car_type, transmission);
+                    manuals.push(builder.build());
+                }
+            }
+            manuals
         }
 
-        pub fn volume(&self) -> f64 {
-            self.volume
+        /// Why a stored car is refused.
+        fn rejection(car: Value) -> String {
+            match serde_json::from_value::<Car>(car) {
+                Ok(_) => panic!("an invalid car is accepted"),
+                Err(err) => err.to_string(),
+            }
         }
 
-        pub fn mileage(&self) -> f64 {
-            self.mileage
+        /// A small xorshift generator, so that generated cases are the same
+        /// on every run.
+        struct Generator(u64);
+
+        /// Parts picked from everything the builders accept.
+        struct Parts {
+            car_type: CarType,
+            seats: u16,
+            volume: f64,
+            mileage: f64,
+            transmission: Transmission,
+            gps_navigator: bool,
+            fuel: f64,
         }
 
-        pub fn go(&mut self, mileage: f64) {
-            if self.started() {
-                self.mileage += mileage;
-            } else {
-                println!("Cannot go(), must start engine first!");
+        /// Breaks generated parts so that the builder refuses them.
+        type Breakage = fn(&mut Parts);
+
+        impl Generator {
+            fn below(&mut self, bound: u64) -> u64 {
+                self.0 ^= self.0 << 13;
+                self.0 ^= self.0 >> 7;
+                self.0 ^= self.0 << 17;
+                self.0 % bound
             }
-        }
-    }
 
-    pub struct GpsNavigator {
-        route: String,
-    }
+            fn parts(&mut self) -> Parts {
+                let car_type = CAR_TYPES[self.below(3) as usize];
+                Parts {
+                    car_type,
+                    seats: 1 + self.below(9) as u16,
+                    volume: (1 + self.below(60)) as f64 / 10.0,
+                    mileage: self.below(300_000) as f64,
+                    transmission: Vec::from(transmissions()).swap_remove(self.below(4) as usize),
+                    gps_navigator: self.below(2) == 0,
+                    fuel: self.below(car_type.tank_capacity() as u64 + 1) as f64,
+                }
+            }
 
-    impl GpsNavigator {
-        pub fn new() -> Self {
-            Self::from_route(
-                "221b, Baker Street, London to Scotland Yard, 8-10 Broadway, London".into(),
-            )
+            /// A car with a few trips in its log.
+            fn car(&mut self) -> Car {
+                let mut builder = CarBuilder::default();
+                let fuel = self.parts().configure(&mut builder);
+                builder.set_fuel(fuel);
+                let mut car = builder.build();
+
+                car.start();
+                for _ in 0..self.below(4) {
+                    // Trips beyond the range are refused, which is fine here.
+                    let _ = car.drive((1 + self.below(200)) as f64);
+                }
+                car.stop();
+                car
+            }
+
+            fn manual(&mut self) -> Manual {
+                let mut builder = CarManualBuilder::default();
+                self.parts().configure(&mut builder);
+                builder.build()
+            }
         }
-        pub fn from_route(route: String) -> Self {
-            Self { route }
+
+        impl Parts {
+            /// Sets the parts on a builder, leaving the fuel to the caller.
+            fn configure(self, builder: &mut impl Builder) -> f64 {
+                builder.set_car_type(self.car_type);
+                builder.set_seats(self.seats);
+                builder.set_engine(Engine::new(self.volume, self.mileage));
+                builder.set_transmission(self.transmission);
+                if self.gps_navigator {
+                    builder.set_gsp_navigator(GpsNavigator::new());
+                }
+                self.fuel
+            }
+
+            /// A car made of these parts without asking the builder.
+            fn assemble(self) -> Car {
+                Car::new(
+                    self.car_type,
+                    self.seats,
+                    Engine::new(self.volume, self.mileage),
+                    self.transmission,
+                    self.gps_navigator.then(GpsNavigator::new),
+                    self.fuel,
+                )
+            }
         }
-        pub fn route(&self) -> &String {
-            &self.route
+
+        #[test]
+        fn generated_cars_round_trip() {
+            let mut generator = Generator(0x5eed);
+            for _ in 0..200 {
+                let car = generator.car();
+
+                let json = serde_json::to_string(&car).unwrap();
+                let restored: Car = serde_json::from_str(&json).unwrap();
+                assert_eq!(serde_json::to_string(&restored).unwrap(), json);
+
+                let binary = bincode::serialize(&car).unwrap();
+                let restored: Car = bincode::deserialize(&binary).unwrap();
+                assert_eq!(serde_json::to_string(&restored).unwrap(), json);
+            }
         }
-    }
-}
 
-mod director {
-    use crate::{
-        builders::Builder,
-        components::{CarType, Engine, GpsNavigator, Transmission},
-    };
-    /// Director knows how to build a car.
-    ///
-    /// However, a builder can build a car manual instead of an actual car,
-    /// everything depends on the concrete builder.
-    pub struct Director;
+        #[test]
+        fn generated_manuals_round_trip() {
+            let mut generator = Generator(0x5eed);
+            for _ in 0..200 {
+                let manual = generator.manual();
 
-    impl Director {
+                let json = serde_json::to_string(&manual).unwrap();
+                let restored: Manual = serde_json::from_str(&json).unwrap();
+                assert_eq!(serde_json::to_string(&restored).unwrap(), json);
+
+                let binary = bincode::serialize(&manual).unwrap();
+                let restored: Manual = bincode::deserialize(&binary).unwrap();
+                assert_eq!(serde_json::to_string(&restored).unwrap(), json);
+            }
+        }
+
+        /// Generated parts broken in a way the builder refuses are refused on
+        /// the way back too, whatever the format.
+        #[test]
+        fn generated_invalid_cars_are_rejected() {
+            let breakages: [(&str, Breakage); 6] = [
+                ("at least one seat", |parts| parts.seats = 0),
+                ("is not positive", |parts| parts.volume = 0.0),
+                ("is not positive", |parts| parts.volume = -1.5),
+                ("is not valid", |parts| parts.mileage = -1.0),
+                ("does not fit into a tank", |parts| parts.fuel = -0.5),
+                ("does not fit into a tank", |parts| {
+                    parts.fuel = parts.car_type.tank_capacity() + 1.0
+                }),
+            ];
+
+            let mut generator = Generator(0x5eed);
+            for _ in 0..50 {
+                for (expected, breakage) in &breakages {
+                    let mut parts = generator.parts();
+                    breakage(&mut parts);
+                    let car = parts.assemble();
+
+                    let err = rejection(serde_json::to_value(&car).unwrap());
+                    assert!(err.contains(expected), "{err}");
+
+                    let binary = bincode::serialize(&car).unwrap();
+                    let err = bincode::deserialize::<Car>(&binary).err().unwrap();
+                    assert!(err.to_string().contains(expected), "{err}");
+                }
+            }
+        }
+
+        #[test]
+        fn cars_round_trip_through_json() {
+            for car in cars() {
+                let json = serde_json::to_string(&car).unwrap();
+                let restored: Car = serde_json::from_str(&json).unwrap();
+
+                assert_eq!(restored.car_type(), car.car_type());
+                assert_eq!(restored.transmission(), car.transmission());
+                assert_eq!(restored.trip_log().trips(), car.trip_log().trips());
+                assert_eq!(serde_json::to_string(&restored).unwrap(), json);
+            }
+        }
+
+        #[test]
+        fn cars_round_trip_through_bincode() {
+            for car in cars() {
+                let binary = bincode::serialize(&car).unwrap();
+                let restored: Car = bincode::deserialize(&binary).unwrap();
+
+                assert_eq!(restored.car_type(), car.car_type());
+                assert_eq!(restored.transmission(), car.transmission());
+                assert_eq!(restored.trip_log().trips(), car.trip_log().trips());
+                assert_eq!(bincode::serialize(&restored).unwrap(), binary);
+            }
+        }
+
+        #[test]
+        fn manuals_round_trip_through_json_and_bincode() {
+            for manual in manuals() {
+                let json = serde_json::to_string(&manual).unwrap();
+                let restored: Manual = serde_json::from_str(&json).unwrap();
+                assert_eq!(serde_json::to_string(&restored).unwrap(), json);
+                assert_eq!(restored.to_string(), manual.to_string());
+
+                let binary = bincode::serialize(&manual).unwrap();
+                let restored: Manual = bincode::deserialize(&binary).unwrap();
+                assert_eq!(bincode::serialize(&restored).unwrap(), binary);
+                assert_eq!(restored.to_string(), manual.to_string());
+            }
+        }
+
+        /// The city car manual is rendered exactly as in `fixtures/`.
+        #[test]
+        fn manual_renders_as_golden_files() {
+            let mut builder = CarManualBuilder::default();
+            Director::construct_city_car(&mut builder);
+            let manual = builder.build();
+            let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures");
+
+            for locale in [Locale::English, Locale::German] {
+                for (format, extension) in [
+                    (Format::PlainText, "txt"),
+                    (Format::Markdown, "md"),
+                    (Format::Html, "html"),
+                ] {
+                    let path = fixtures.join(format!("manual.{}.{}", locale.code(), extension));
+                    let expected = fs::read_to_string(&path).unwrap();
+                    assert_eq!(
+                        manual.render(format, locale),
+                        expected,
+                        "{}",
+                        path.display()
+                    );
+                }
+            }
+        }
+
+        #[test]
+        fn transmissions_are_translated() {
+            for (manual, expected) in manuals().into_iter().zip([
+                "Eingang-Getriebe",
+                "Schaltgetriebe",
+                "Automatik",
+                "Halbautomatik",
+            ]) {
+                let text = manual.render(Format::PlainText, Locale::German);
+                assert!(text.contains(&format!("Getriebe: {expected}\n")), "{text}");
+            }
+        }
+
+        #[test]
+        fn route_is_driven_as_one_trip() {
+            let mut car = cars().remove(0);
+            let route = car
+                .gps_navigator()
+                .as_ref()
+                .unwrap()
+                .plan("victoria", "oxford_circus", RouteKind::Fastest)
+                .unwrap();
+
+            car.start();
+            let trip = car.drive_route(&route).unwrap();
+
+            assert_eq!(trip.distance, route.distance());
+            assert_eq!(car.trip_log().trips().last(), Some(&trip));
+        }
+
+        #[test]
+        fn route_to_the_same_place_is_an_empty_trip() {
+            let mut car = cars().remove(0);
+            let route = car
+                .gps_navigator()
+                .as_ref()
+                .unwrap()
+                .plan("victoria", "victoria", RouteKind::Shortest)
+                .unwrap();
+            let (fuel, odometer, trips) =
+                (car.fuel(), car.odometer(), car.trip_log().trips().len());
+
+            let trip = car.drive_route(&route).unwrap();
+
+            assert_eq!(trip.distance, 0.0);
+            assert_eq!(trip.fuel_used, 0.0);
+            assert_eq!((trip.fuel_left, trip.odometer_end), (fuel, odometer));
+            assert_eq!(car.trip_log().trips().len(), trips);
+        }
+
+        /// A city car burning 5.85 l/100 km, with the default fuel in the tank.
+        fn city_car(mileage: f64) -> Car {
+            let mut builder = CarBuilder::default();
+            builder.set_car_type(CarType::CityCar);
+            builder.set_seats(4);
+            builder.set_engine(Engine::new(1.0, mileage));
+            builder.set_transmission(Transmission::Manual);
+            builder.build()
+        }
+
+        #[test]
+        fn car_does_not_drive_with_engine_off() {
+            let mut car = city_car(0.0);
+
+            assert_eq!(car.drive(10.0).unwrap_err(), TripError::EngineOff);
+            assert_eq!((car.fuel(), car.odometer()), (5.0, 0.0));
+            assert!(car.trip_log().trips().is_empty());
+        }
+
+        #[test]
+        fn car_does_not_drive_invalid_distances() {
+            let mut car = city_car(0.0);
+            car.start();
+
+            for distance in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+                let err = car.drive(distance).unwrap_err();
+                assert!(
+                    matches!(err, TripError::InvalidDistance(_)),
+                    "{distance}: {err:?}"
+                );
+            }
+            assert_eq!((car.fuel(), car.odometer()), (5.0, 0.0));
+            assert!(car.trip_log().trips().is_empty());
+        }
+
+        #[test]
+        fn car_does_not_drive_beyond_its_range() {
+            let mut car = city_car(0.0);
+            car.start();
+            let range = car.range();
+
+            assert_eq!(
+                car.drive(range + 1.0).unwrap_err(),
+                TripError::OutOfRange {
+                    distance: range + 1.0,
+                    range
+                }
+            );
+            assert_eq!((car.fuel(), car.odometer()), (5.0, 0.0));
+
+            car.drive(range).unwrap();
+            assert!(car.fuel().abs() < 1e-9, "{}", car.fuel());
+        }
+
+        #[test]
+        fn refuel_is_clamped_to_tank_capacity() {
+            let mut car = city_car(0.0);
+
+            assert_eq!(car.refuel(-5.0), 0.0);
+            assert_eq!(car.refuel(10.0), 10.0);
+            assert_eq!(car.refuel(100.0), 20.0);
+            assert_eq!(car.fuel(), CarType::CityCar.tank_capacity());
+            assert_eq!(car.refuel(1.0), 0.0);
+        }
+
+        #[test]
+        fn odometer_counts_every_trip() {
+            let mut car = city_car(1000.0);
+            car.start();
+            car.drive(10.0).unwrap();
+            car.drive(30.0).unwrap();
+
+            assert_eq!(car.odometer(), 1040.0);
+            let trips = car.trip_log().trips();
+            assert_eq!(
+                (trips[0].odometer_start, trips[0].odometer_end),
+                (1000.0, 1010.0)
+            );
+            assert_eq!(
+                (trips[1].odometer_start, trips[1].odometer_end),
+                (1010.0, 1040.0)
+            );
+            assert_eq!(car.trip_log().total_distance(), 40.0);
+        }
+
+        #[test]
+        fn trip_log_is_exported_as_csv() {
+            let mut car = city_car(1000.0);
+            car.start();
+            car.drive(10.0).unwrap();
+            car.drive(30.0).unwrap();
+
+            let mut csv = vec![];
+            car.trip_log().export_csv(&mut csv).unwrap();
+
+            assert_eq!(
+                String::from_utf8(csv).unwrap(),
+                "trip,distance,fuel_used,fuel_left,odometer_start,odometer_end\n\
+                 1,10.00,0.585,4.415,1000.00,1010.00\n\
+                 2,30.00,1.755,2.660,1010.00,1040.00\n"
+            );
+        }
+
+        #[test]
+        fn stored_car_without_seats_is_rejected() {
+            let car = Car::new(
+                CarType::CityCar,
+                0,
+                Engine::new(1.2, 0.0),
+                Transmission::Automatic,
+                None,
+                5.0,
+            );
+
+            let err = rejection(serde_json::to_value(&car).unwrap());
+            assert!(err.contains("at least one seat"), "{err}");
+
+            let binary = bincode::serialize(&car).unwrap();
+            assert!(bincode::deserialize::<Car>(&binary).is_err());
+
+            let mut manual = serde_json::to_value(&car).unwrap();
+            manual
+                .as_object_mut()
+                .unwrap()
+                .retain(|key, _| key != "fuel" && key != "trip_log");
+            assert!(serde_json::from_value::<Manual>(manual).is_err());
+        }
+
+        #[test]
+        fn stored_car_with_fuel_above_tank_capacity_is_rejected() {
+            let car = Car::new(
+                CarType::CityCar,
+                2,
+                Engine::new(1.2, 0.0),
+                Transmission::Automatic,
+                None,
+                CarType::CityCar.tank_capacity() + 1.0,
+            );
+
+            let err = rejection(serde_json::to_value(&car).unwrap());
+            assert!(err.contains("does not fit into a tank"), "{err}");
+
+            let binary = bincode::serialize(&car).unwrap();
+            assert!(bincode::deserialize::<Car>(&binary).is_err());
+        }
+
+        #[test]
+        fn stored_car_with_invalid_map_is_rejected() {
+            let mut car = serde_json::to_value(&cars()[0]).unwrap();
+            car["gps_navigator"] = json!(
+                "place baker_street 0.0 0.0 Baker Street\nroad baker_street nowhere 1.0 20 Nowhere Lane"
+            );
+
+            let err = rejection(car);
+            assert!(err.contains("line 2: unknown place `nowhere`"), "{err}");
+        }
+    }
+}
+
+mod components {
+    use std::path::Path;
+
+    use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+    use crate::navigation::{MapError, RoadGraph, Route, RouteError, RouteKind};
+
+    #[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+    pub enum CarType {
+        CityCar,
+        SportsCar,
+        Suv,
+    }
+
+    impl CarType {
+        /// How much the body type scales the engine's fuel consumption.
+        pub fn consumption_factor(self) -> f64 {
+            match self {
+                CarType::CityCar => 0.9,
+                CarType::SportsCar => 1.4,
+                CarType::Suv => 1.25,
+            }
+        }
+
+        /// Fuel tank capacity in litres.
+        pub fn tank_capacity(self) -> f64 {
+            match self {
+                CarType::CityCar => 35.0,
+                CarType::SportsCar => 60.0,
+                CarType::Suv => 70.0,
+            }
+        }
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    pub enum Transmission {
+        SingleSpeed,
+        Manual,
+        Automatic,
+        SemiAutomatic,
+    }
+
+    /// An engine is always stored switched off.
+    #[derive(Serialize, Deserialize)]
+    pub struct Engine {
+        volume: f64,
+        mileage: f64,
+        #[serde(skip)]
+        started: bool,
+    }
+
+    impl Engine {
+        pub fn new(volume: f64, mileage: f64) -> Self {
+            Self {
+                volume,
+                mileage,
+                started: false,
+            }
+        }
+
+        pub fn on(&mut self) {
+            self.started = true;
+        }
+
+        pub fn off(&mut self) {
+            self.started = false;
+        }
+
+        pub fn started(&self) -> bool {
+            self.started
+        }
+
+        pub fn volume(&self) -> f64 {
+            self.volume
+        }
+
+        pub fn mileage(&self) -> f64 {
+            self.mileage
+        }
+
+        pub fn go(&mut self, mileage: f64) {
+            if self.started() {
+                self.mileage += mileage;
+            } else {
+                println!("Cannot go(), must start engine first!");
+            }
+        }
+    }
+
+    /// Plans routes over a road map.
+    pub struct GpsNavigator {
+        map: RoadGraph,
+    }
+
+    impl GpsNavigator {
+        /// A navigator preloaded with the map of central London.
+        pub fn new() -> Self {
+            Self::from_map(
+                RoadGraph::parse(include_str!("../maps/london.map"))
+                    .expect("built-in map is valid"),
+            )
+        }
+
+        pub fn from_map(map: RoadGraph) -> Self {
+            Self { map }
+        }
+
+        pub fn from_file(path: impl AsRef<Path>) -> Result<Self, MapError> {
+            RoadGraph::load(path).map(Self::from_map)
+        }
+
+        pub fn map(&self) -> &RoadGraph {
+            &self.map
+        }
+
+        pub fn plan(&self, from: &str, to: &str, kind: RouteKind) -> Result<Route, RouteError> {
+            self.map.route(from, to, kind)
+        }
+    }
+
+    /// A navigator is stored as the text of its map, which is parsed and
+    /// validated again on the way back.
+    impl Serialize for GpsNavigator {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.collect_str(&self.map)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for GpsNavigator {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let map = String::deserialize(deserializer)?;
+            RoadGraph::parse(&map)
+                .map(Self::from_map)
+                .map_err(de::Error::custom)
+        }
+    }
+}
+
+mod navigation {
+    // mod.rs
+    // ------
+    // mod graph;
+    // mod route;
+    pub use graph::{MapError, RoadGraph};
+    pub use route::{Route, RouteError, RouteKind};
+
+    mod graph {
+        use std::{fmt, fs, io, path::Path};
+
+        pub struct Place {
+            pub id: String,
+            pub name: String,
+            pub x: f64,
+            pub y: f64,
+        }
+
+        pub struct Road {
+            pub to: usize,
+            pub distance: f64,
+            pub speed_limit: f64,
+            pub street: String,
+        }
+
+        /// Places connected by two-way roads.
+        ///
+        /// A map is a plain text file, one record per line:
+        /// `place <id> <x km> <y km> <name>` and
+        /// `road <from> <to> <distance km> <speed limit km/h> <street>`.
+        /// Empty lines and lines starting with `#` are ignored.
+        #[derive(Default)]
+        pub struct RoadGraph {
+            places: Vec<Place>,
+            roads: Vec<Vec<Road>>,
+        }
+
+        #[derive(Debug)]
+        pub enum MapError {
+            Io(io::Error),
+            Parse { line: usize, reason: String },
+        }
+
+        impl fmt::Display for MapError {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                match self {
+                    MapError::Io(err) => write!(f, "cannot read the map: {err}"),
+                    MapError::Parse { line, reason } => write!(f, "line {line}: {reason}"),
+                }
+            }
+        }
+
+        impl std::error::Error for MapError {}
+
+        impl From<io::Error> for MapError {
+            fn from(err: io::Error) -> Self {
+                MapError::Io(err)
+            }
+        }
+
+        impl RoadGraph {
+            pub fn load(path: impl AsRef<Path>) -> Result<Self, MapError> {
+                Self::parse(&fs::read_to_string(path)?)
+            }
+
+            pub fn parse(map: &str) -> Result<Self, MapError> {
+                let mut graph = Self::default();
+
+                for (number, line) in map.lines().enumerate() {
+                    let line = line.trim();
+                    if line.is_empty() || line.starts_with('#') {
+                        continue;
+                    }
+
+                    graph.parse_record(line).map_err(|reason| MapError::Parse {
+                        line: number + 1,
+                        reason,
+                    })?;
+                }
+
+                Ok(graph)
+            }
+
+            fn parse_record(&mut self, line: &str) -> Result<(), String> {
+                let mut rest = line;
+                // Takes the next whitespace separated field off the line.
+                let mut field = |what: &str| {
+                    let line = rest.trim_start();
+                    let (field, tail) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+                    rest = tail;
+                    Some(field)
+                        .filter(|field| !field.is_empty())
+                        .ok_or(format!("missing {what}"))
+                };
+                // Names may contain spaces, so they take the rest of the line.
+                let name = |rest: &str, what: &str| {
+                    Some(rest.trim())
+                        .filter(|name| !name.is_empty())
+                        .map(String::from)
+                        .ok_or(format!("missing {what}"))
+                };
+                let number = |value: &str| {
+                    value
+                        .parse::<f64>()
+                        .ok()
+                        .filter(|value| value.is_finite())
+                        .ok_or(format!("`{value}` is not a number"))
+                };
+
+                match field("record type")? {
+                    "place" => {
+                        let id = field("place id")?;
+                        let x = number(field("x coordinate")?)?;
+                        let y = number(field("y coordinate")?)?;
+                        let name = name(rest, "place name")?;
+
+                        if self.find(id).is_some() {
+                            return Err(format!("place `{id}` is defined twice"));
+                        }
+
+                        self.places.push(Place {
+                            id: id.into(),
+                            name,
+                            x,
+                            y,
+                        });
+                        self.roads.push(vec![]);
+                    }
+                    "road" => {
+                        let from = self.place_index(field("road start")?)?;
+                        let to = self.place_index(field("road end")?)?;
+                        let distance = number(field("distance")?)?;
+                        let speed_limit = number(field("speed limit")?)?;
+                        let street = name(rest, "street name")?;
+
+                        if from == to {
+                            return Err("road must connect two different places".into());
+                        }
+
+                        if distance < self.straight_line(from, to) {
+                            return Err(
+                                "road is shorter than the straight line between its ends".into()
+                            );
+                        }
+
+                        if speed_limit <= 0.0 {
+                            return Err("speed limit must be positive".into());
+                        }
+
+                        for (from, to) in [(from, to), (to, from)] {
+                            self.roads[from].push(Road {
+                                to,
+                                distance,
+                                speed_limit,
+                                street: street.clone(),
+                            });
+                        }
+                    }
+                    other => return Err(format!("unknown record `{other}`")),
+                }
+
+                Ok(())
+            }
+
+            fn place_index(&self, id: &str) -> Result<usize, String> {
+                self.find(id).ok_or(format!("unknown place `{id}`"))
+            }
+
+            pub fn find(&self, id: &str) -> Option<usize> {
+                self.places.iter().position(|place| place.id == id)
+            }
+
+            pub fn place(&self, index: usize) -> &Place {
+                &self.places[index]
+            }
+
+            pub fn places(&self) -> &[Place] {
+                &self.places
+            }
+
+            pub fn roads_from(&self, index: usize) -> &[Road] {
+                &self.roads[index]
+            }
+
+            pub fn max_speed_limit(&self) -> f64 {
+                self.roads
+                    .iter()
+                    .flatten()
+                    .map(|road| road.speed_limit)
+                    .fold(0.0, f64::max)
+            }
+
+            pub fn straight_line(&self, from: usize, to: usize) -> f64 {
+                let (from, to) = (&self.places[from], &self.places[to]);
+                (from.x - to.x).hypot(from.y - to.y)
+            }
+        }
+
+        /// Writes the graph back in the map file format.
+        impl fmt::Display for RoadGraph {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                for place in &self.places {
+                    writeln!(
+                        f,
+                        "place {} {} {} {}",
+                        place.id, place.x, place.y, place.name
+                    )?;
+                }
+
+                for (from, roads) in self.roads.iter().enumerate() {
+                    // Every road is stored in both directions, write it once.
+                    for road in roads.iter().filter(|road| from < road.to) {
+                        writeln!(
+                            f,
+                            "road {} {} {} {} {}",
+                            self.places[from].id,
+                            self.places[road.to].id,
+                            road.distance,
+                            road.speed_limit,
+                            road.street
+                        )?;
+                    }
+                }
+
+                Ok(())
+            }
+        }
+    }
+
+    mod route {
+        use std::{cmp::Ordering, collections::BinaryHeap, fmt, time::Duration};
+
+        use super::graph::RoadGraph;
+
+        #[derive(Clone, Copy, Debug)]
+        pub enum RouteKind {
+            /// The least distance to drive.
+            Shortest,
+            /// The least time to drive, keeping to speed limits.
+            Fastest,
+        }
+
+        /// A stretch of a route along a single street.
+        pub struct Leg {
+            pub street: String,
+            pub to: String,
+            pub distance: f64,
+            pub eta: Duration,
+        }
+
+        /// Turn-by-turn directions between two places.
+        pub struct Route {
+            from: String,
+            legs: Vec<Leg>,
+        }
+
+        #[derive(Debug, PartialEq)]
+        pub enum RouteError {
+            UnknownPlace(String),
+            Unreachable { from: String, to: String },
+        }
+
+        impl fmt::Display for RouteError {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                match self {
+                    RouteError::UnknownPlace(id) => write!(f, "`{id}` is not on the map"),
+                    RouteError::Unreachable { from, to } => {
+                        write!(f, "there is no road from `{from}` to `{to}`")
+                    }
+                }
+            }
+        }
+
+        impl std::error::Error for RouteError {}
+
+        impl Route {
+            pub fn legs(&self) -> &[Leg] {
+                &self.legs
+            }
+
+            pub fn distance(&self) -> f64 {
+                self.legs
+                    .iter()
+                    .fold(0.0, |total, leg| total + leg.distance)
+            }
+
+            pub fn eta(&self) -> Duration {
+                self.legs.iter().map(|leg| leg.eta).sum()
+            }
+        }
+
+        impl fmt::Display for Route {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                writeln!(f, "Start at {}", self.from)?;
+                for (number, leg) in self.legs.iter().enumerate() {
+                    writeln!(
+                        f,
+                        "{}. Follow {} to {}: {:.1} km, {} min",
+                        number + 1,
+                        leg.street,
+                        leg.to,
+                        leg.distance,
+                        minutes(leg.eta)
+                    )?;
+                }
+                write!(
+                    f,
+                    "Total: {:.1} km, {} min",
+                    self.distance(),
+                    minutes(self.eta())
+                )
+            }
+        }
+
+        fn minutes(duration: Duration) -> u64 {
+            (duration.as_secs_f64() / 60.0).round() as u64
+        }
+
+        /// A place waiting in the A* open set, ordered by the lowest
+        /// estimated total cost first.
+        struct Candidate {
+            estimate: f64,
+            place: usize,
+        }
+
+        impl PartialEq for Candidate {
+            fn eq(&self, other: &Self) -> bool {
+                self.cmp(other) == Ordering::Equal
+            }
+        }
+
+        impl Eq for Candidate {}
+
+        impl PartialOrd for Candidate {
+            fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+                Some(self.cmp(other))
+            }
+        }
+
+        impl Ord for Candidate {
+            fn cmp(&self, other: &Self) -> Ordering {
+                other.estimate.total_cmp(&self.estimate)
+            }
+        }
+
+        impl RoadGraph {
+            /// Finds the best route between two places with A*.
+            ///
+            /// The straight line to the destination is the heuristic: it never
+            /// overestimates the remaining distance, nor the remaining time when
+            /// driven at the highest speed limit on the map.
+            pub fn route(
+                &self,
+                from: &str,
+                to: &str,
+                kind: RouteKind,
+            ) -> Result<Route, RouteError> {
+                let start = self
+                    .find(from)
+                    .ok_or_else(|| RouteError::UnknownPlace(from.into()))?;
+                let goal = self
+                    .find(to)
+                    .ok_or_else(|| RouteError::UnknownPlace(to.into()))?;
+
+                let max_speed = self.max_speed_limit();
+                let heuristic = |place: usize| match kind {
+                    RouteKind::Shortest => self.straight_line(place, goal),
+                    RouteKind::Fastest => self.straight_line(place, goal) / max_speed,
+                };
+
+                let mut cost = vec![f64::INFINITY; self.places().len()];
+                let mut came_by: Vec<Option<(usize, usize)>> = vec![None; self.places().len()];
+                let mut open = BinaryHeap::new();
+
+                cost[start] = 0.0;
+                open.push(Candidate {
+                    estimate: heuristic(start),
+                    place: start,
+                });
+
+                while let Some(Candidate { estimate, place }) = open.pop() {
+                    if place == goal {
+                        break;
+                    }
+
+                    if estimate > cost[place] + heuristic(place) {
+                        // A better path to this place has been found already.
+                        continue;
+                    }
+
+                    for (index, road) in self.roads_from(place).iter().enumerate() {
+                        let step = match kind {
+                            RouteKind::Shortest => road.distance,
+                            RouteKind::Fastest => road.distance / road.speed_limit,
+                        };
+
+                        if cost[place] + step < cost[road.to] {
+                            cost[road.to] = cost[place] + step;
+                            came_by[road.to] = Some((place, index));
+                            open.push(Candidate {
+                                estimate: cost[road.to] + heuristic(road.to),
+                                place: road.to,
+                            });
+                        }
+                    }
+                }
+
+                if cost[goal].is_infinite() {
+                    return Err(RouteError::Unreachable {
+                        from: from.into(),
+                        to: to.into(),
+                    });
+                }
+
+                let mut path = vec![];
+                let mut place = goal;
+                while let Some((previous, index)) = came_by[place] {
+                    path.push(&self.roads_from(previous)[index]);
+                    place = previous;
+                }
+
+                let mut legs: Vec<Leg> = vec![];
+                for road in path.into_iter().rev() {
+                    let eta = Duration::from_secs_f64(road.distance / road.speed_limit * 3600.0);
+                    let to = self.place(road.to).name.clone();
+
+                    match legs.last_mut() {
+                        Some(leg) if leg.street == road.street => {
+                            leg.to = to;
+                            leg.distance += road.distance;
+                            leg.eta += eta;
+                        }
+                        _ => legs.push(Leg {
+                            street: road.street.clone(),
+                            to,
+                            distance: road.distance,
+                            eta,
+                        }),
+                    }
+                }
+
+                Ok(Route {
+                    from: self.place(start).name.clone(),
+                    legs,
+                })
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::{MapError, RoadGraph, RouteError, RouteKind};
+
+        fn london() -> RoadGraph {
+            RoadGraph::parse(include_str!("../maps/london.map")).unwrap()
+        }
+
+        fn streets(graph: &RoadGraph, kind: RouteKind) -> Vec<String> {
+            let route = graph.route("baker_street", "scotland_yard", kind).unwrap();
+            route.legs().iter().map(|leg| leg.street.clone()).collect()
+        }
+
+        /// Why a map is refused, with the line it is refused at.
+        fn error(map: &str) -> String {
+            match RoadGraph::parse(map) {
+                Ok(_) => panic!("an invalid map is accepted"),
+                Err(err) => err.to_string(),
+            }
+        }
+
+        #[test]
+        fn shortest_and_fastest_routes_differ() {
+            let london = london();
+            let shortest = london
+                .route("baker_street", "scotland_yard", RouteKind::Shortest)
+                .unwrap();
+            let fastest = london
+                .route("baker_street", "scotland_yard", RouteKind::Fastest)
+                .unwrap();
+
+            assert_eq!(
+                streets(&london, RouteKind::Shortest),
+                [
+                    "Baker Street",
+                    "Park Lane",
+                    "Grosvenor Place",
+                    "Victoria Street"
+                ]
+            );
+            assert_eq!(
+                streets(&london, RouteKind::Fastest),
+                [
+                    "Baker Street",
+                    "Park Lane",
+                    "Birdcage Walk",
+                    "Tothill Street"
+                ]
+            );
+            assert!(shortest.distance() < fastest.distance());
+            assert!(fastest.eta() < shortest.eta());
+        }
+
+        #[test]
+        fn legs_along_one_street_are_joined() {
+            let map = "place a 0 0 A\nplace b 1 0 B\nplace c 2 0 C\n\
+                       road a b 1 30 High Street\nroad b c 1 30 High Street";
+            let route = RoadGraph::parse(map)
+                .unwrap()
+                .route("a", "c", RouteKind::Shortest)
+                .unwrap();
+
+            assert_eq!(
+                route.to_string(),
+                "Start at A\n1. Follow High Street to C: 2.0 km, 4 min\nTotal: 2.0 km, 4 min"
+            );
+        }
+
+        #[test]
+        fn unreachable_place_has_no_route() {
+            let map = "place a 0 0 A\nplace b 1 0 B\nplace island 5 5 Island\n\
+                       road a b 1 30 High Street";
+            let graph = RoadGraph::parse(map).unwrap();
+
+            for kind in [RouteKind::Shortest, RouteKind::Fastest] {
+                assert_eq!(
+                    graph.route("a", "island", kind).err(),
+                    Some(RouteError::Unreachable {
+                        from: "a".into(),
+                        to: "island".into()
+                    })
+                );
+            }
+        }
+
+        #[test]
+        fn route_to_unknown_place_fails() {
+            assert_eq!(
+                london()
+                    .route("baker_street", "nowhere", RouteKind::Shortest)
+                    .err(),
+                Some(RouteError::UnknownPlace("nowhere".into()))
+            );
+        }
+
+        #[test]
+        fn invalid_maps_are_rejected_at_their_line() {
+            let places = "# two places\nplace a 0 0 A\nplace b 3 4 B\n";
+            for (record, expected) in [
+                (
+                    "road a nowhere 5 30 Lane",
+                    "line 4: unknown place `nowhere`",
+                ),
+                (
+                    "road nowhere a 5 30 Lane",
+                    "line 4: unknown place `nowhere`",
+                ),
+                ("road a b", "line 4: missing distance"),
+                ("road a b 5 30", "line 4: missing street name"),
+                ("road a b five 30 Lane", "line 4: `five` is not a number"),
+                ("road a b inf 30 Lane", "line 4: `inf` is not a number"),
+                (
+                    "road a a 5 30 Lane",
+                    "line 4: road must connect two different places",
+                ),
+                (
+                    "road a b 4 30 Lane",
+                    "line 4: road is shorter than the straight line between its ends",
+                ),
+                ("road a b 5 0 Lane", "line 4: speed limit must be positive"),
+                ("place a 1 1 Again", "line 4: place `a` is defined twice"),
+                ("place c 1", "line 4: missing y coordinate"),
+                ("place c 1 1", "line 4: missing place name"),
+                ("bridge a b", "line 4: unknown record `bridge`"),
+            ] {
+                assert_eq!(error(&format!("{places}{record}")), expected, "{record}");
+            }
+        }
+
+        #[test]
+        fn missing_map_file_fails_to_load() {
+            let err = RoadGraph::load("maps/nowhere.map").err().unwrap();
+            assert!(matches!(err, MapError::Io(_)), "{err}");
+        }
+
+        #[test]
+        fn map_is_written_back_as_it_is_read() {
+            let london = london().to_string();
+            assert_eq!(RoadGraph::parse(&london).unwrap().to_string(), london);
+        }
+    }
+}
+
+mod rendering {
+    // mod.rs
+    // ------
+    // mod catalog;
+    // mod format;
+    pub use catalog::Locale;
+    pub use format::{Document, Format};
+
+    mod catalog {
+        use std::collections::HashMap;
+
+        #[derive(Clone, Copy, Debug)]
+        pub enum Locale {
+            English,
+            German,
+        }
+
+        impl Locale {
+            /// Language code, as used by HTML.
+            pub fn code(self) -> &'static str {
+                match self {
+                    Locale::English => "en",
+                    Locale::German => "de",
+                }
+            }
+
+            pub fn catalog(self) -> Catalog {
+                let english = Catalog::parse(include_str!("../locales/en.catalog"));
+                match self {
+                    Locale::English => english,
+                    Locale::German => {
+                        Catalog::parse(include_str!("../locales/de.catalog")).or(english)
+                    }
+                }
+            }
+        }
+
+        /// Translated strings looked up by key.
+        ///
+        /// A catalog is a text file with a `<key> = <text>` pair per line,
+        /// empty lines and lines starting with `#` are ignored.
+        #[derive(Default)]
+        pub struct Catalog {
+            strings: HashMap<String, String>,
+        }
+
+        impl Catalog {
+            pub fn parse(catalog: &str) -> Self {
+                let strings = catalog
+                    .lines()
+                    .map(str::trim)
+                    .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                    .filter_map(|line| line.split_once('='))
+                    .map(|(key, text)| (key.trim().into(), text.trim().into()))
+                    .collect();
+
+                Self { strings }
+            }
+
+            /// Falls back to another catalog for missing strings.
+            pub fn or(mut self, fallback: Catalog) -> Self {
+                for (key, text) in fallback.strings {
+                    self.strings.entry(key).or_insert(text);
+                }
+                self
+            }
+
+            /// Returns a translated string, or the key itself if there is none.
+            pub fn get<'a>(&'a self, key: &'a str) -> &'a str {
+                self.strings.get(key).map_or(key, String::as_str)
+            }
+
+            /// Returns a translated string with `{name}` placeholders filled in.
+            pub fn format(&self, key: &str, values: &[(&str, String)]) -> String {
+                values
+                    .iter()
+                    .fold(self.get(key).to_string(), |text, (name, value)| {
+                        text.replace(&format!("{{{}}}", name), value)
+                    })
+            }
+        }
+    }
+
+    mod format {
+        use std::fmt::Write;
+
+        use super::Locale;
+
+        #[derive(Clone, Copy, Debug)]
+        pub enum Format {
+            PlainText,
+            Markdown,
+            Html,
+        }
+
+        /// Translated content ready to be rendered into any format.
+        pub struct Document {
+            pub locale: Locale,
+            pub title: String,
+            pub entries: Vec<(String, String)>,
+        }
+
+        impl Document {
+            pub fn render(&self, format: Format) -> String {
+                match format {
+                    Format::PlainText => self.plain_text(),
+                    Format::Markdown => self.markdown(),
+                    Format::Html => self.html(),
+                }
+            }
+
+            fn plain_text(&self) -> String {
+                let mut text = String::new();
+                for (label, value) in &self.entries {
+                    writeln!(text, "{}: {}", label, value).unwrap();
+                }
+                text
+            }
+
+            fn markdown(&self) -> String {
+                let mut text = format!("# {}\n\n", escape_markdown(&self.title));
+                for (label, value) in &self.entries {
+                    writeln!(
+                        text,
+                        "- **{}:** {}",
+                        escape_markdown(label),
+                        escape_markdown(value)
+                    )
+                    .unwrap();
+                }
+                text
+            }
+
+            fn html(&self) -> String {
+                let title = escape(&self.title);
+                let mut text = String::new();
+
+                writeln!(text, "<!DOCTYPE html>").unwrap();
+                writeln!(text, "<html lang=\"{}\">", self.locale.code()).unwrap();
+                writeln!(text, "<head>").unwrap();
+                writeln!(text, "<meta charset=\"utf-8\">").unwrap();
+                writeln!(text, "<title>{}</title>", title).unwrap();
+                writeln!(text, "</head>").unwrap();
+                writeln!(text, "<body>").unwrap();
+                writeln!(text, "<h1>{}</h1>", title).unwrap();
+                writeln!(text, "<dl>").unwrap();
+                for (label, value) in &self.entries {
+                    writeln!(text, "<dt>{}</dt>", escape(label)).unwrap();
+                    writeln!(text, "<dd>{}</dd>", escape(value)).unwrap();
+                }
+                writeln!(text, "</dl>").unwrap();
+                writeln!(text, "</body>").unwrap();
+                writeln!(text, "</html>").unwrap();
+                text
+            }
+        }
+
+        fn escape(text: &str) -> String {
+            text.replace('&', "&amp;")
+                .replace('<', "&lt;")
+                .replace('>', "&gt;")
+                .replace('"', "&quot;")
+        }
+
+        /// Backslash-escapes the characters that Markdown would otherwise
+        /// take as emphasis, code, links, headings, tables or inline HTML.
+        fn escape_markdown(text: &str) -> String {
+            let mut escaped = String::with_capacity(text.len());
+            for c in text.chars() {
+                if matches!(
+                    c,
+                    '\\' | '`' | '*' | '_' | '[' | ']' | '<' | '>' | '#' | '&' | '|' | '!'
+                ) {
+                    escaped.push('\\');
+                }
+                escaped.push(c);
+            }
+            escaped
+        }
+
+        #[cfg(test)]
+        mod tests {
+            use super::{Document, Format};
+            use crate::rendering::Locale;
+
+            fn document() -> Document {
+                Document {
+                    locale: Locale::English,
+                    title: "Fast & <furious>".into(),
+                    entries: vec![("*Bold* claim".into(), "[link](#top) | `code`".into())],
+                }
+            }
+
+            #[test]
+            fn markdown_escapes_metacharacters() {
+                assert_eq!(
+                    document().render(Format::Markdown),
+                    "# Fast \\& \\<furious\\>\n\n\
+                     - **\\*Bold\\* claim:** \\[link\\](\\#top) \\| \\`code\\`\n"
+                );
+            }
+
+            #[test]
+            fn html_escapes_metacharacters() {
+                let html = document().render(Format::Html);
+                assert!(
+                    html.contains("<h1>Fast &amp; &lt;furious&gt;</h1>"),
+                    "{html}"
+                );
+                assert!(html.contains("<dd>[link](#top) | `code`</dd>"), "{html}");
+            }
+        }
+    }
+}
+
+mod fleet {
+    // mod.rs
+    // ------
+    // mod query;
+    // mod report;
+    // mod vin;
+    pub use query::Query;
+    pub use report::UtilizationReport;
+    pub use vin::Vin;
+
+    use std::{collections::BTreeMap, fmt, fs, io, path::Path};
+
+    use serde::{Deserialize, Serialize};
+
+    use crate::cars::Car;
+
+    /// Cars built by `CarBuilder`, registered under their VINs.
+    #[derive(Default, Serialize, Deserialize)]
+    pub struct Fleet {
+        cars: BTreeMap<Vin, Car>,
+    }
+
+    #[derive(Debug)]
+    pub enum FleetError {
+        InvalidVin(String),
+        DuplicateVin(Vin),
+        Io(io::Error),
+        Format(serde_json::Error),
+    }
+
+    impl fmt::Display for FleetError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                FleetError::InvalidVin(vin) => write!(f, "`{}` is not a valid VIN", vin),
+                FleetError::DuplicateVin(vin) => write!(f, "{} is already registered", vin),
+                FleetError::Io(err) => write!(f, "cannot access the fleet file: {}", err),
+                FleetError::Format(err) => write!(f, "invalid fleet file: {}", err),
+            }
+        }
+    }
+
+    impl std::error::Error for FleetError {}
+
+    impl From<io::Error> for FleetError {
+        fn from(err: io::Error) -> Self {
+            FleetError::Io(err)
+        }
+    }
+
+    impl From<serde_json::Error> for FleetError {
+        fn from(err: serde_json::Error) -> Self {
+            FleetError::Format(err)
+        }
+    }
+
+    impl Fleet {
+        /// Restores a fleet saved with `save`, validating every car again.
+        pub fn load(path: impl AsRef<Path>) -> Result<Self, FleetError> {
+            let file = io::BufReader::new(fs::File::open(path)?);
+            Ok(serde_json::from_reader(file)?)
+        }
+
+        pub fn save(&self, path: impl AsRef<Path>) -> Result<(), FleetError> {
+            let file = io::BufWriter::new(fs::File::create(path)?);
+            Ok(serde_json::to_writer_pretty(file, self)?)
+        }
+
+        pub fn register(&mut self, vin: &str, car: Car) -> Result<Vin, FleetError> {
+            let vin = Vin::parse(vin).ok_or_else(|| FleetError::InvalidVin(vin.into()))?;
+
+            if self.cars.contains_key(&vin) {
+                return Err(FleetError::DuplicateVin(vin));
+            }
+
+            self.cars.insert(vin.clone(), car);
+            Ok(vin)
+        }
+
+        pub fn get_mut(&mut self, vin: &Vin) -> Option<&mut Car> {
+            self.cars.get_mut(vin)
+        }
+
+        pub fn cars(&self) -> impl Iterator<Item = (&Vin, &Car)> {
+            self.cars.iter()
+        }
+
+        /// Cars matching every criterion of a query, ordered by VIN.
+        pub fn find<'a>(&'a self, query: &'a Query) -> impl Iterator<Item = (&'a Vin, &'a Car)> {
+            self.cars().filter(|(_, car)| query.matches(car))
+        }
+
+        pub fn total_fuel(&self) -> f64 {
+            self.cars.values().map(Car::fuel).sum()
+        }
+
+        pub fn total_mileage(&self) -> f64 {
+            self.cars.values().map(Car::odometer).sum()
+        }
+
+        pub fn utilization(&self) -> UtilizationReport {
+            UtilizationReport::new(self)
+        }
+    }
+
+    mod vin {
+        use std::fmt;
+
+        use serde::{Deserialize, Serialize};
+
+        /// A vehicle identification number: 17 digits and capital letters,
+        /// except `I`, `O` and `Q` that are too easy to confuse with digits.
+        #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+        #[serde(try_from = "String")]
+        pub struct Vin(String);
+
+        impl Vin {
+            pub fn parse(vin: &str) -> Option<Self> {
+                let valid = vin.len() == 17
+                    && vin.chars().all(|c| {
+                        c.is_ascii_digit() || (c.is_ascii_uppercase() && !"IOQ".contains(c))
+                    });
+
+                valid.then(|| Vin(vin.into()))
+            }
+        }
+
+        impl TryFrom<String> for Vin {
+            type Error = String;
+
+            fn try_from(vin: String) -> Result<Self, Self::Error> {
+                Vin::parse(&vin).ok_or_else(|| format!("`{}` is not a valid VIN", vin))
+            }
+        }
+
+        impl fmt::Display for Vin {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str(&self.0)
+            }
+        }
+    }
+
+    mod query {
+        use crate::{
+            cars::Car,
+            components::{CarType, Transmission},
+        };
+
+        /// Criteria to look cars up in a fleet, an empty query matches any car.
+        #[derive(Default)]
+        pub struct Query {
+            car_type: Option<CarType>,
+            transmission: Option<Transmission>,
+            gps_navigator: Option<bool>,
+        }
+
+        impl Query {
+            pub fn car_type(mut self, car_type: CarType) -> Self {
+                self.car_type = Some(car_type);
+                self
+            }
+
+            pub fn transmission(mut self, transmission: Transmission) -> Self {
+                self.transmission = Some(transmission);
+                self
+            }
+
+            pub fn gps_navigator(mut self, available: bool) -> Self {
+                self.gps_navigator = Some(available);
+                self
+            }
+
+            pub fn matches(&self, car: &Car) -> bool {
+                self.car_type
+                    .is_none_or(|car_type| car.car_type() == car_type)
+                    && self
+                        .transmission
+                        .as_ref()
+                        .is_none_or(|transmission| car.transmission() == transmission)
+                    && self
+                        .gps_navigator
+                        .is_none_or(|available| car.gps_navigator().is_some() == available)
+            }
+        }
+    }
+
+    mod report {
+        use std::fmt;
+
+        use super::{Fleet, Vin};
+        use crate::components::CarType;
+
+        pub struct CarUtilization {
+            pub vin: Vin,
+            pub car_type: CarType,
+            pub seats: u16,
+            pub trips: usize,
+            pub distance: f64,
+            pub fuel_used: f64,
+            pub fuel_left: f64,
+        }
+
+        /// How much each car of a fleet has been driven.
+        pub struct UtilizationReport {
+            cars: Vec<CarUtilization>,
+        }
+
+        impl UtilizationReport {
+            pub(super) fn new(fleet: &Fleet) -> Self {
+                let cars = fleet
+                    .cars()
+                    .map(|(vin, car)| CarUtilization {
+                        vin: vin.clone(),
+                        car_type: car.car_type(),
+                        seats: car.seats(),
+                        trips: car.trip_log().trips().len(),
+                        distance: car.trip_log().total_distance(),
+                        fuel_used: car.trip_log().total_fuel_used(),
+                        fuel_left: car.fuel(),
+                    })
+                    .collect();
+
+                Self { cars }
+            }
+
+            pub fn total_distance(&self) -> f64 {
+                self.cars
+                    .iter()
+                    .fold(0.0, |total, car| total + car.distance)
+            }
+
+            /// Cars that haven't made a single trip.
+            pub fn idle(&self) -> impl Iterator<Item = &CarUtilization> {
+                self.cars.iter().filter(|car| car.trips == 0)
+            }
+        }
+
+        impl fmt::Display for UtilizationReport {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                let total_distance = self.total_distance();
+
+                writeln!(
+                    f,
+                    "{:<17}  {:<9}  {:>5}  {:>5}  {:>9}  {:>9}  {:>9}  {:>5}",
+                    "VIN", "Type", "Seats", "Trips", "Distance", "Fuel used", "Fuel left", "Share"
+                )?;
+
+                for car in &self.cars {
+                    let share = if total_distance > 0.0 {
+                        car.distance / total_distance * 100.0
+                    } else {
+                        0.0
+                    };
+
+                    writeln!(
+                        f,
+                        "{:<17}  {:<9}  {:>5}  {:>5}  {:>6.1} km  {:>7.2} l  {:>7.2} l  {:>4.0}%",
+                        car.vin.to_string(),
+                        format!("{:?}", car.car_type),
+                        car.seats,
+                        car.trips,
+                        car.distance,
+                        car.fuel_used,
+                        car.fuel_left,
+                        share
+                    )?;
+                }
+
+                write!(
+                    f,
+                    "Total: {:.1} km driven, {} of {} cars idle",
+                    total_distance,
+                    self.idle().count(),
+                    self.cars.len()
+                )
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use std::fs;
+
+        use super::{Fleet, FleetError, Query, Vin};
+        use crate::{
+            builders::{Builder, CarBuilder},
+            cars::Car,
+            components::{CarType, Engine, GpsNavigator, Transmission},
+        };
+
+        const CITY: &str = "1HGCM82633A004352";
+        const SPORTS: &str = "WP0ZZZ99ZTS392124";
+        const SUV: &str = "5YJ3E1EA7KF317000";
+
+        fn car(car_type: CarType, transmission: Transmission, gps_navigator: bool) -> Car {
+            let mut builder = CarBuilder::default();
+            builder.set_car_type(car_type);
+            builder.set_seats(4);
+            builder.set_engine(Engine::new(1.0, 1000.0));
+            builder.set_transmission(transmission);
+            if gps_navigator {
+                builder.set_gsp_navigator(GpsNavigator::new());
+            }
+            builder.build()
+        }
+
+        /// A city car driven 40 km, a sports car driven 20 km and an idle SUV.
+        fn fleet() -> Fleet {
+            let mut fleet = Fleet::default();
+            for (vin, car) in [
+                (CITY, car(CarType::CityCar, Transmission::Manual, true)),
+                (
+                    SPORTS,
+                    car(CarType::SportsCar, Transmission::Automatic, false),
+                ),
+                (SUV, car(CarType::Suv, Transmission::Automatic, true)),
+            ] {
+                fleet.register(vin, car).unwrap();
+            }
+
+            for (vin, distances) in [(CITY, &[10.0, 30.0][..]), (SPORTS, &[20.0])] {
+                let car = fleet.get_mut(&Vin::parse(vin).unwrap()).unwrap();
+                car.start();
+                for &distance in distances {
+                    car.drive(distance).unwrap();
+                }
+                car.stop();
+            }
+            fleet
+        }
+
+        fn found(fleet: &Fleet, query: Query) -> Vec<String> {
+            fleet.find(&query).map(|(vin, _)| vin.to_string()).collect()
+        }
+
+        #[test]
+        fn vins_are_checked() {
+            assert!(Vin::parse(CITY).is_some());
+            for vin in [
+                "",
+                "1HGCM82633A00435",
+                "1HGCM82633A0043521",
+                "1HGCM82633A00435I",
+                "1HGCM82633A00435O",
+                "1HGCM82633A00435Q",
+                "1hgcm82633a004352",
+                "1HGCM82633A00435-",
+            ] {
+                assert!(Vin::parse(vin).is_none(), "{vin}");
+            }
+        }
+
+        #[test]
+        fn invalid_vin_is_not_registered() {
+            let mut fleet = Fleet::default();
+            let err = fleet
+                .register(
+                    "not a VIN",
+                    car(CarType::CityCar, Transmission::Manual, false),
+                )
+                .unwrap_err();
+
+            assert!(matches!(&err, FleetError::InvalidVin(vin) if vin == "not a VIN"));
+            assert_eq!(err.to_string(), "`not a VIN` is not a valid VIN");
+            assert_eq!(fleet.cars().count(), 0);
+        }
+
+        #[test]
+        fn duplicate_vin_is_not_registered() {
+            let mut fleet = fleet();
+            let err = fleet
+                .register(SUV, car(CarType::CityCar, Transmission::Manual, false))
+                .unwrap_err();
+
+            assert!(matches!(&err, FleetError::DuplicateVin(vin) if vin.to_string() == SUV));
+            assert_eq!(err.to_string(), format!("{SUV} is already registered"));
+            assert_eq!(fleet.cars().count(), 3);
+        }
+
+        #[test]
+        fn queries_filter_cars() {
+            let fleet = fleet();
+
+            assert_eq!(found(&fleet, Query::default()), [CITY, SUV, SPORTS]);
+            assert_eq!(
+                found(&fleet, Query::default().car_type(CarType::Suv)),
+                [SUV]
+            );
+            assert_eq!(
+                found(
+                    &fleet,
+                    Query::default().transmission(Transmission::Automatic)
+                ),
+                [SUV, SPORTS]
+            );
+            assert_eq!(
+                found(&fleet, Query::default().gps_navigator(true)),
+                [CITY, SUV]
+            );
+            assert_eq!(
+                found(&fleet, Query::default().gps_navigator(false)),
+                [SPORTS]
+            );
+            assert_eq!(
+                found(
+                    &fleet,
+                    Query::default()
+                        .transmission(Transmission::Automatic)
+                        .gps_navigator(true)
+                ),
+                [SUV]
+            );
+            assert!(found(
+                &fleet,
+                Query::default()
+                    .car_type(CarType::CityCar)
+                    .transmission(Transmission::Automatic)
+            )
+            .is_empty());
+        }
+
+        #[test]
+        fn fleet_is_saved_and_loaded() {
+            let fleet = fleet();
+            let dir = tempfile::tempdir().unwrap();
+            let path = dir.path().join("fleet.json");
+
+            fleet.save(&path).unwrap();
+            let loaded = Fleet::load(&path).unwrap();
+
+            assert_eq!(
+                serde_json::to_string(&loaded).unwrap(),
+                serde_json::to_string(&fleet).unwrap()
+            );
+            assert_eq!(loaded.total_mileage(), fleet.total_mileage());
+        }
+
+        #[test]
+        fn fleet_with_invalid_car_is_not_loaded() {
+            let dir = tempfile::tempdir().unwrap();
+            let path = dir.path().join("fleet.json");
+            fleet().save(&path).unwrap();
+            let saved = fs::read_to_string(&path).unwrap();
+            fs::write(&path, saved.replacen("\"seats\": 4", "\"seats\": 0", 1)).unwrap();
+
+            let err = Fleet::load(&path).err().unwrap();
+            assert!(matches!(err, FleetError::Format(_)), "{err}");
+            assert!(err.to_string().contains("at least one seat"), "{err}");
+
+            let err = Fleet::load(dir.path().join("missing.json")).err().unwrap();
+            assert!(matches!(err, FleetError::Io(_)), "{err}");
+        }
+
+        #[test]
+        fn utilization_report_totals_every_car() {
+            let fleet = fleet();
+            let report = fleet.utilization();
+
+            assert_eq!(report.total_distance(), 60.0);
+            assert_eq!(fleet.total_mileage(), 3060.0);
+            assert_eq!(
+                report
+                    .idle()
+                    .map(|car| car.vin.to_string())
+                    .collect::<Vec<_>>(),
+                [SUV]
+            );
+            assert_eq!(
+                report.to_string(),
+                "VIN                Type       Seats  Trips   Distance  Fuel used  Fuel left  Share\n\
+                 1HGCM82633A004352  CityCar        4      2    40.0 km     2.34 l     2.66 l    67%\n\
+                 5YJ3E1EA7KF317000  Suv            4      0     0.0 km     0.00 l     5.00 l     0%\n\
+                 WP0ZZZ99ZTS392124  SportsCar      4      1    20.0 km     1.82 l     3.18 l    33%\n\
+                 Total: 60.0 km driven, 1 of 3 cars idle"
+            );
+        }
+
+        #[test]
+        fn empty_fleet_report_has_no_shares() {
+            assert_eq!(
+                Fleet::default().utilization().to_string(),
+                "VIN                Type       Seats  Trips   Distance  Fuel used  Fuel left  Share\n\
+                 Total: 0.0 km driven, 0 of 0 cars idle"
+            );
+        }
+    }
+}
+
+mod director {
+    use crate::{
+        builders::Builder,
+        components::{CarType, Engine, GpsNavigator, Transmission},
+    };
+    /// Director knows how to build a car.
+    ///
+    /// However, a builder can build a car manual instead of an actual car,
+    /// everything depends on the concrete builder.
+    pub struct Director;
+
+    impl Director {
         pub fn construct_sports_car(builder: &mut impl Builder) {
             builder.set_car_type(CarType::SportsCar);
             builder.set_seats(2);
@@ -365,8 +2625,12 @@ mod director {
 
 fn main() {
     use crate::builders::{Builder, CarBuilder, CarManualBuilder};
-    use cars::{Car, Manual};
+    use cars::{Car, Manual, TripError};
+    use components::{CarType, GpsNavigator, Transmission};
     use director::Director;
+    use fleet::{Fleet, Query, Vin};
+    use navigation::RouteKind;
+    use rendering::{Format, Locale};
 
     let mut car_builder = CarBuilder::default();
 
@@ -378,9 +2642,107 @@ fn main() {
     // The final product is often retrieved from a builder object, since
     // Director is not aware and not dependent on concrete builders and
     // products.
-    let car: Car = car_builder.build();
+    let mut car: Car = car_builder.build();
     println!("Car built: {:?}\n", car.car_type());
 
+    // A recipe can be tweaked before building, e.g. an SUV with
+    // a single-speed gearbox.
+    let mut builder = CarBuilder::default();
+    Director::construct_suv(&mut builder);
+    builder.set_transmission(Transmission::SingleSpeed);
+    let custom: Car = builder.build();
+    println!(
+        "{:?} built with {} seats, a {} l engine and a {:?} gearbox, burning {:.1} l per 100 km\n",
+        custom.car_type(),
+        custom.seats(),
+        custom.engine().volume(),
+        custom.transmission(),
+        custom.consumption()
+    );
+
+    // The built car is ready to be driven.
+    if let Err(err) = car.drive(10.0) {
+        println!("Trip refused: {}", err);
+    }
+
+    car.start();
+    for distance in [12.5, 40.0] {
+        if let Err(TripError::OutOfRange { range, .. }) = car.drive(distance) {
+            println!("Only {:.1} km of range left, refuelling", range);
+            car.refuel(50.0);
+            car.drive(distance).expect("enough fuel after refuelling");
+        }
+
+        println!("Drove {} km, {:.2} l of fuel left", distance, car.fuel());
+    }
+
+    // A car with a navigator can plan its trips over the road map.
+    if let Some(gps_navigator) = car.gps_navigator() {
+        for kind in [RouteKind::Shortest, RouteKind::Fastest] {
+            let route = gps_navigator
+                .plan("baker_street", "scotland_yard", kind)
+                .expect("Scotland Yard is on the map");
+            println!("\n{:?} route:\n{}", kind, route);
+        }
+    }
+
+    let route = car
+        .gps_navigator()
+        .as_ref()
+        .map(|gps_navigator| gps_navigator.plan("victoria", "oxford_circus", RouteKind::Fastest));
+
+    match route {
+        Some(Ok(route)) => {
+            let trip = car.drive_route(&route).expect("the route is within range");
+            println!(
+                "\nDrove {:.1} km in {} turns from Victoria to Oxford Circus",
+                trip.distance,
+                route.legs().len()
+            );
+        }
+        Some(Err(err)) => println!("\nNo route: {}", err),
+        None => println!("\nNo GPS navigator to plan a route"),
+    }
+    car.stop();
+
+    println!(
+        "\nTrip log: {:.1} km, {:.2} l, odometer at {:.1} km",
+        car.trip_log().total_distance(),
+        car.trip_log().total_fuel_used(),
+        car.odometer()
+    );
+    car.trip_log()
+        .export_csv(std::io::stdout())
+        .expect("trip log is written to stdout");
+    println!();
+
+    // A car is stored as JSON or in a compact binary form, and comes back
+    // through the builder, so it is checked the same way as a new one.
+    let json = serde_json::to_string(&car).expect("a car is serializable");
+    let binary = bincode::serialize(&car).expect("a car is serializable");
+    println!(
+        "\nCar stored in {} bytes of JSON or {} bytes of binary",
+        json.len(),
+        binary.len()
+    );
+
+    // A navigator can also be loaded from a map file on disk.
+    let mut suv_builder = CarBuilder::default();
+    Director::construct_suv(&mut suv_builder);
+    match GpsNavigator::from_file(concat!(env!("CARGO_MANIFEST_DIR"), "/maps/london.map")) {
+        Ok(gps_navigator) => suv_builder.set_gsp_navigator(gps_navigator),
+        Err(err) => println!("Cannot load the map: {}", err),
+    }
+
+    // Tower Bridge is not on the map, so this plan fails on purpose.
+    let suv: Car = suv_builder.build();
+    match suv.gps_navigator().as_ref().map(|gps_navigator| {
+        gps_navigator.plan("baker_street", "tower_bridge", RouteKind::Shortest)
+    }) {
+        Some(Err(err)) => println!("{:?} cannot get there: {}\n", suv.car_type(), err),
+        _ => println!("{:?} is ready to go\n", suv.car_type()),
+    }
+
     let mut manual_builder = CarManualBuilder::default();
 
     // Director may know several building recipes.
@@ -389,4 +2751,71 @@ fn main() {
     // The final car manual.
     let manual: Manual = manual_builder.build();
     println!("Car manual built:\n{}", manual);
+
+    // The same manual can be rendered in other formats and languages.
+    println!("{}", manual.render(Format::Markdown, Locale::German));
+    println!("{}", manual.render(Format::Html, Locale::English));
+
+    // Built cars are kept in a fleet under their VINs.
+    let mut fleet = Fleet::default();
+    let recipes = [
+        (
+            "1HGCM82633A004352",
+            Director::construct_city_car as fn(&mut CarBuilder),
+        ),
+        ("WP0ZZZ99ZTS392124", Director::construct_sports_car),
+        ("SALVA2AE4EH877482", Director::construct_suv),
+    ];
+    let vins: Vec<Vin> = recipes
+        .into_iter()
+        .map(|(vin, construct)| {
+            let mut builder = CarBuilder::default();
+            construct(&mut builder);
+            fleet
+                .register(vin, builder.build())
+                .expect("a new valid VIN")
+        })
+        .collect();
+
+    let mut builder = CarBuilder::default();
+    Director::construct_city_car(&mut builder);
+    if let Err(err) = fleet.register("WP0ZZZ99ZTS392124", builder.build()) {
+        println!("Cannot register: {}", err);
+    }
+
+    if let Some(city_car) = fleet.get_mut(&vins[0]) {
+        city_car.start();
+        city_car.drive(30.0).expect("the trip is within range");
+        city_car.stop();
+    }
+
+    let query = Query::default()
+        .gps_navigator(true)
+        .transmission(Transmission::Manual);
+    for (vin, car) in fleet.find(&query) {
+        println!(
+            "{} is a {:?} with a manual gearbox and GPS",
+            vin,
+            car.car_type()
+        );
+    }
+
+    let query = Query::default().car_type(CarType::SportsCar);
+    println!(
+        "{} sports car(s), {:.1} l of fuel and {:.1} km driven across the fleet\n",
+        fleet.find(&query).count(),
+        fleet.total_fuel(),
+        fleet.total_mileage()
+    );
+    println!("{}", fleet.utilization());
+
+    // The fleet is kept in a local file between runs.
+    let path = std::env::temp_dir().join("builder-fleet.json");
+    fleet.save(&path).expect("the fleet is saved");
+    let restored = Fleet::load(&path).expect("the saved fleet is valid");
+    println!(
+        "\nFleet of {} cars restored from {}",
+        restored.cars().count(),
+        path.display()
+    );
 }
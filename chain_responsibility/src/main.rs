@@ -9,185 +9,2304 @@ Demo: processing a patient through a chain of departments
     Patient -> Reception -> Doctor -> Medical -> Cashier
 */
 
+// Generic chain
+mod handler {
+    // mod branch;
+    // mod fan_out;
+    // mod link;
+    // mod pipeline;
+    pub use branch::Branch;
+    pub use fan_out::{FanOut, Fork};
+    pub use link::Link;
+    pub use pipeline::Pipeline;
+
+    /// What a handler decided about a request.
+    #[derive(Debug, PartialEq)]
+    pub enum Outcome<Resp> {
+        /// Pass the request on to the next handler.
+        Continue,
+        /// Nothing to do for this request here, pass it on.
+        Skip,
+        /// The request is handled, no other handler is needed.
+        Stop(Resp),
+        /// The request cannot be handled.
+        Reject(Resp),
+    }
+
+    impl<Resp> Outcome<Resp> {
+        /// Whether the request goes on to the next handler.
+        pub fn passes(&self) -> bool {
+            matches!(self, Outcome::Continue | Outcome::Skip)
+        }
+    }
+
+    /// A single role of objects that make up a chain, for any kind of request.
+    ///
+    /// Handlers don't know about each other: they are put in order either by
+    /// linking them one to another with `Link`, or by listing them in
+    /// a `Pipeline`. A chain may split with a `Branch` or a `FanOut`, and
+    /// rejoins at the handler that follows them.
+    pub trait Handler<Req, Resp> {
+        fn handle(&mut self, request: &mut Req) -> Outcome<Resp>;
+        fn name(&self) -> &'static str;
+    }
+
+    mod branch {
+        use super::{Handler, Outcome};
+
+        type Route<Req, Resp> = (Box<dyn Fn(&Req) -> bool>, Box<dyn Handler<Req, Resp>>);
+
+        /// Sends a request down the first route whose condition holds.
+        /// Whichever route is taken, the request then goes on to the handler
+        /// after the branch.
+        pub struct Branch<Req, Resp> {
+            name: &'static str,
+            routes: Vec<Route<Req, Resp>>,
+            otherwise: Option<Box<dyn Handler<Req, Resp>>>,
+        }
+
+        impl<Req, Resp> Branch<Req, Resp> {
+            pub fn new(name: &'static str) -> Self {
+                Self {
+                    name,
+                    routes: vec![],
+                    otherwise: None,
+                }
+            }
+
+            /// Adds a route, which is checked after the ones added before it.
+            pub fn when(
+                mut self,
+                condition: impl Fn(&Req) -> bool + 'static,
+                handler: impl Handler<Req, Resp> + 'static,
+            ) -> Self {
+                self.routes.push((Box::new(condition), Box::new(handler)));
+                self
+            }
+
+            /// Sets the route to take when no condition holds. Without it,
+            /// such requests skip the branch.
+            pub fn otherwise(mut self, handler: impl Handler<Req, Resp> + 'static) -> Self {
+                self.otherwise = Some(Box::new(handler));
+                self
+            }
+        }
+
+        impl<Req, Resp> Handler<Req, Resp> for Branch<Req, Resp> {
+            fn handle(&mut self, request: &mut Req) -> Outcome<Resp> {
+                let route = self
+                    .routes
+                    .iter_mut()
+                    .find(|(condition, _)| condition(request))
+                    .map(|(_, handler)| handler)
+                    .or(self.otherwise.as_mut());
+
+                match route {
+                    Some(handler) => handler.handle(request),
+                    None => Outcome::Skip,
+                }
+            }
+
+            fn name(&self) -> &'static str {
+                self.name
+            }
+        }
+
+        #[cfg(test)]
+        mod tests {
+            use super::Branch;
+            use crate::{
+                department::{Lab, Outcome, Radiology},
+                handler::Handler,
+                patient::{Finding, Patient, Test},
+            };
+
+            fn diagnostics() -> Branch<Patient, String> {
+                Branch::new("diagnostics")
+                    .when(
+                        |patient: &Patient| patient.findings.contains(&Finding::SuspectedFracture),
+                        Radiology,
+                    )
+                    .when(
+                        |patient: &Patient| patient.findings.contains(&Finding::SuspectedInfection),
+                        Lab,
+                    )
+            }
+
+            fn patient(findings: Vec<Finding>) -> Patient {
+                Patient {
+                    name: "Eve".into(),
+                    findings,
+                    ..Patient::default()
+                }
+            }
+
+            fn departments(patient: &Patient) -> Vec<&'static str> {
+                patient
+                    .trace
+                    .steps()
+                    .iter()
+                    .map(|step| step.department)
+                    .collect()
+            }
+
+            #[test]
+            fn findings_decide_the_route() {
+                for (finding, department, test) in [
+                    (Finding::SuspectedFracture, "radiology", Test::XRay),
+                    (Finding::SuspectedInfection, "lab", Test::BloodTest),
+                ] {
+                    let mut patient = patient(vec![finding]);
+
+                    assert_eq!(diagnostics().handle(&mut patient), Outcome::Continue);
+                    assert_eq!(patient.tests, [test]);
+                    assert_eq!(departments(&patient), [department]);
+                }
+            }
+
+            #[test]
+            fn first_route_that_holds_is_taken() {
+                let mut patient = patient(vec![
+                    Finding::SuspectedInfection,
+                    Finding::SuspectedFracture,
+                ]);
+
+                diagnostics().handle(&mut patient);
+
+                assert_eq!(patient.tests, [Test::XRay]);
+                assert_eq!(departments(&patient), ["radiology"]);
+            }
+
+            #[test]
+            fn requests_without_a_route_skip_the_branch_or_take_the_other_one() {
+                let mut patient = patient(vec![]);
+                assert_eq!(diagnostics().handle(&mut patient), Outcome::Skip);
+                assert!(departments(&patient).is_empty());
+
+                let mut branch = diagnostics().otherwise(Lab);
+                assert_eq!(branch.handle(&mut patient), Outcome::Skip);
+                assert_eq!(departments(&patient), ["lab"]);
+                assert_eq!(branch.name(), "diagnostics");
+            }
+        }
+    }
+
+    mod fan_out {
+        use std::thread;
+
+        use super::{Handler, Outcome};
+
+        /// A request that can be copied for handlers working side by side,
+        /// and put back together afterwards.
+        pub trait Fork: Clone {
+            /// Takes in what a handler did to a copy of the request made at
+            /// `fork_point`.
+            fn join(&mut self, fork_point: &Self, branch: Self);
+        }
+
+        /// Hands a copy of a request to each of several handlers at once,
+        /// every one on its own thread, then joins the copies back in the
+        /// order the handlers are listed.
+        ///
+        /// The first handler that doesn't pass the request decides the
+        /// outcome; otherwise the request goes on.
+        pub struct FanOut<Req, Resp> {
+            name: &'static str,
+            handlers: Vec<Box<dyn Handler<Req, Resp> + Send>>,
+        }
+
+        impl<Req, Resp> FanOut<Req, Resp> {
+            pub fn new(
+                name: &'static str,
+                handlers: Vec<Box<dyn Handler<Req, Resp> + Send>>,
+            ) -> Self {
+                Self { name, handlers }
+            }
+        }
+
+        impl<Req: Fork + Send, Resp: Send> Handler<Req, Resp> for FanOut<Req, Resp> {
+            fn handle(&mut self, request: &mut Req) -> Outcome<Resp> {
+                let fork_point = request.clone();
+
+                let results: Vec<(Req, Outcome<Resp>)> = thread::scope(|scope| {
+                    let workers: Vec<_> = self
+                        .handlers
+                        .iter_mut()
+                        .map(|handler| {
+                            let mut branch = fork_point.clone();
+                            scope.spawn(move || {
+                                let outcome = handler.handle(&mut branch);
+                                (branch, outcome)
+                            })
+                        })
+                        .collect();
+
+                    workers
+                        .into_iter()
+                        .map(|worker| worker.join().expect("a fan-out handler panicked"))
+                        .collect()
+                });
+
+                let mut decision = Outcome::Skip;
+                for (branch, outcome) in results {
+                    request.join(&fork_point, branch);
+                    decision = match (decision, outcome) {
+                        (decision, _) if !decision.passes() => decision,
+                        (Outcome::Skip, outcome) => outcome,
+                        (_, outcome) if !outcome.passes() => outcome,
+                        (decision, _) => decision,
+                    };
+                }
+
+                decision
+            }
+
+            fn name(&self) -> &'static str {
+                self.name
+            }
+        }
+
+        #[cfg(test)]
+        mod tests {
+            use super::{FanOut, Fork};
+            use crate::handler::{Handler, Outcome};
+
+            /// Names of the handlers that have seen a request.
+            #[derive(Clone, Default)]
+            struct Seen(Vec<&'static str>);
+
+            impl Fork for Seen {
+                fn join(&mut self, fork_point: &Self, branch: Self) {
+                    self.0.extend(branch.0.into_iter().skip(fork_point.0.len()));
+                }
+            }
+
+            type Decide = fn() -> Outcome<&'static str>;
+
+            /// Notes its name on a request, and decides as it's told.
+            struct Says {
+                name: &'static str,
+                outcome: Decide,
+            }
+
+            impl Handler<Seen, &'static str> for Says {
+                fn handle(&mut self, request: &mut Seen) -> Outcome<&'static str> {
+                    request.0.push(self.name);
+                    (self.outcome)()
+                }
+
+                fn name(&self) -> &'static str {
+                    self.name
+                }
+            }
+
+            fn fan_out(outcomes: [Decide; 3]) -> FanOut<Seen, &'static str> {
+                let handlers = ["a", "b", "c"]
+                    .into_iter()
+                    .zip(outcomes)
+                    .map(|(name, outcome)| Box::new(Says { name, outcome }) as _)
+                    .collect();
+                FanOut::new("fan-out", handlers)
+            }
+
+            #[test]
+            fn first_listed_handler_that_stops_or_rejects_decides() {
+                let cases: [([Decide; 3], Outcome<&'static str>); 5] = [
+                    (
+                        [|| Outcome::Skip, || Outcome::Skip, || Outcome::Skip],
+                        Outcome::Skip,
+                    ),
+                    (
+                        [|| Outcome::Skip, || Outcome::Continue, || Outcome::Skip],
+                        Outcome::Continue,
+                    ),
+                    (
+                        [
+                            || Outcome::Continue,
+                            || Outcome::Skip,
+                            || Outcome::Stop("c"),
+                        ],
+                        Outcome::Stop("c"),
+                    ),
+                    (
+                        [
+                            || Outcome::Skip,
+                            || Outcome::Reject("b"),
+                            || Outcome::Stop("c"),
+                        ],
+                        Outcome::Reject("b"),
+                    ),
+                    (
+                        [
+                            || Outcome::Stop("a"),
+                            || Outcome::Reject("b"),
+                            || Outcome::Continue,
+                        ],
+                        Outcome::Stop("a"),
+                    ),
+                ];
+
+                for (outcomes, decision) in cases {
+                    let mut seen = Seen(vec!["before"]);
+
+                    assert_eq!(fan_out(outcomes).handle(&mut seen), decision);
+                    // Every handler runs, and is joined in the listed order.
+                    assert_eq!(seen.0, ["before", "a", "b", "c"]);
+                }
+            }
+        }
+    }
+
+    mod link {
+        use super::{Handler, Outcome};
+
+        /// A handler with a link to the next one.
+        pub struct Link<Req, Resp> {
+            handler: Box<dyn Handler<Req, Resp>>,
+            next: Option<Box<Link<Req, Resp>>>,
+        }
+
+        impl<Req, Resp> Link<Req, Resp> {
+            pub fn new(handler: impl Handler<Req, Resp> + 'static) -> Self {
+                Self {
+                    handler: Box::new(handler),
+                    next: None,
+                }
+            }
+
+            /// Appends a handler to the end of the chain.
+            pub fn then(mut self, handler: impl Handler<Req, Resp> + 'static) -> Self {
+                let mut last = &mut self;
+                while let Some(ref mut next) = last.next {
+                    last = next;
+                }
+                last.next = Some(Box::new(Link::new(handler)));
+                self
+            }
+
+            /// Runs the chain starting with this link, and returns the outcome
+            /// of the handler that stopped it, or `Continue` if the request has
+            /// gone through the whole chain.
+            pub fn execute(&mut self, request: &mut Req) -> Outcome<Resp> {
+                match self.handler.handle(request) {
+                    outcome if outcome.passes() => match &mut self.next {
+                        Some(next) => next.execute(request),
+                        None => Outcome::Continue,
+                    },
+                    outcome => outcome,
+                }
+            }
+        }
+
+        /// A whole chain can be a single handler in another one.
+        impl<Req, Resp> Handler<Req, Resp> for Link<Req, Resp> {
+            fn handle(&mut self, request: &mut Req) -> Outcome<Resp> {
+                self.execute(request)
+            }
+
+            fn name(&self) -> &'static str {
+                self.handler.name()
+            }
+        }
+    }
+
+    mod pipeline {
+        use super::{Handler, Outcome};
+
+        /// Handlers listed in order, which can be rearranged at runtime.
+        pub struct Pipeline<Req, Resp> {
+            handlers: Vec<Box<dyn Handler<Req, Resp>>>,
+        }
+
+        impl<Req, Resp> Default for Pipeline<Req, Resp> {
+            fn default() -> Self {
+                Self { handlers: vec![] }
+            }
+        }
+
+        impl<Req, Resp> Pipeline<Req, Resp> {
+            pub fn new(handlers: Vec<Box<dyn Handler<Req, Resp>>>) -> Self {
+                Self { handlers }
+            }
+
+            /// Runs handlers one after another until one of them stops, and
+            /// returns its outcome, or `Continue` if none did.
+            pub fn execute(&mut self, request: &mut Req) -> Outcome<Resp> {
+                for handler in &mut self.handlers {
+                    let outcome = handler.handle(request);
+                    if !outcome.passes() {
+                        return outcome;
+                    }
+                }
+
+                Outcome::Continue
+            }
+
+            /// Names of the handlers in the order they run.
+            pub fn names(&self) -> Vec<&'static str> {
+                self.handlers.iter().map(|handler| handler.name()).collect()
+            }
+
+            pub fn push(&mut self, handler: impl Handler<Req, Resp> + 'static) {
+                self.handlers.push(Box::new(handler));
+            }
+
+            /// Inserts a handler at a position, or at the end if the pipeline
+            /// is shorter than that.
+            pub fn insert(&mut self, index: usize, handler: impl Handler<Req, Resp> + 'static) {
+                let index = index.min(self.handlers.len());
+                self.handlers.insert(index, Box::new(handler));
+            }
+
+            /// Takes the first handler with a given name out of the pipeline.
+            pub fn remove(&mut self, name: &str) -> Option<Box<dyn Handler<Req, Resp>>> {
+                let index = self
+                    .handlers
+                    .iter()
+                    .position(|handler| handler.name() == name)?;
+                Some(self.handlers.remove(index))
+            }
+
+            /// Moves the first handler with a given name to a new position.
+            /// Returns `false` if there is no such handler.
+            pub fn move_to(&mut self, name: &str, index: usize) -> bool {
+                match self.remove(name) {
+                    Some(handler) => {
+                        let index = index.min(self.handlers.len());
+                        self.handlers.insert(index, handler);
+                        true
+                    }
+                    None => false,
+                }
+            }
+        }
+
+        impl<Req, Resp> Handler<Req, Resp> for Pipeline<Req, Resp> {
+            fn handle(&mut self, request: &mut Req) -> Outcome<Resp> {
+                self.execute(request)
+            }
+
+            fn name(&self) -> &'static str {
+                "pipeline"
+            }
+        }
+    }
+}
+
 // Request
 mod patient {
-    #[derive(Default)]
+    use std::fmt::{self, Write};
+
+    use crate::{
+        handler::Fork,
+        trace::{Status, Trace},
+    };
+
+    /// How soon a patient must be seen, from the least to the most urgent.
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+    pub enum Priority {
+        NonUrgent,
+        #[default]
+        Standard,
+        Urgent,
+        Emergency,
+    }
+
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    pub enum Symptom {
+        Fever,
+        Cough,
+        Headache,
+        Pain,
+        Rash,
+    }
+
+    /// What a doctor suspects and wants to be tested.
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    pub enum Finding {
+        SuspectedFracture,
+        SuspectedInfection,
+    }
+
+    impl Finding {
+        pub fn for_symptom(symptom: Symptom) -> Option<Self> {
+            match symptom {
+                Symptom::Pain => Some(Finding::SuspectedFracture),
+                Symptom::Fever | Symptom::Cough => Some(Finding::SuspectedInfection),
+                Symptom::Headache | Symptom::Rash => None,
+            }
+        }
+    }
+
+    /// Money in cents.
+    pub type Cents = u32;
+
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    pub enum Test {
+        XRay,
+        BloodTest,
+    }
+
+    impl Test {
+        pub fn price(self) -> Cents {
+            match self {
+                Test::XRay => 9500,
+                Test::BloodTest => 3800,
+            }
+        }
+    }
+
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    pub enum Medicine {
+        Paracetamol,
+        CoughSyrup,
+        Ibuprofen,
+        Antihistamine,
+    }
+
+    impl Medicine {
+        /// The medicine a doctor prescribes for a symptom.
+        pub fn for_symptom(symptom: Symptom) -> Self {
+            match symptom {
+                Symptom::Fever => Medicine::Paracetamol,
+                Symptom::Cough => Medicine::CoughSyrup,
+                Symptom::Headache | Symptom::Pain => Medicine::Ibuprofen,
+                Symptom::Rash => Medicine::Antihistamine,
+            }
+        }
+
+        pub fn price(self) -> Cents {
+            match self {
+                Medicine::Paracetamol => 450,
+                Medicine::CoughSyrup => 890,
+                Medicine::Ibuprofen => 620,
+                Medicine::Antihistamine => 1150,
+            }
+        }
+    }
+
+    #[derive(Clone)]
+    pub struct Prescription {
+        pub medicine: Medicine,
+        pub dispensed: bool,
+    }
+
+    /// Insurance pays for a visit, except for a fixed co-pay on the
+    /// consultation and the uncovered share of tests and medicine.
+    #[derive(Clone)]
+    pub struct Insurance {
+        pub provider: String,
+        pub consultation_co_pay: Cents,
+        /// Percentage of test and medicine prices paid by the insurer.
+        pub coverage: u8,
+    }
+
+    #[derive(Clone)]
+    pub struct Bill {
+        pub consultation: Cents,
+        pub tests: Cents,
+        pub medicine: Cents,
+        pub covered: Cents,
+    }
+
+    impl Bill {
+        /// The amount the patient has to pay.
+        pub fn due(&self) -> Cents {
+            self.consultation + self.tests + self.medicine - self.covered
+        }
+    }
+
+    impl fmt::Display for Bill {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(
+                f,
+                "{} due (consultation {}, tests {}, medicine {}, covered {})",
+                money(self.due()),
+                money(self.consultation),
+                money(self.tests),
+                money(self.medicine),
+                money(self.covered)
+            )
+        }
+    }
+
+    fn money(cents: Cents) -> String {
+        format!("${}.{:02}", cents / 100, cents % 100)
+    }
+
+    #[derive(Clone, Default)]
     pub struct Patient {
         pub name: String,
+        pub priority: Priority,
+        pub symptoms: Vec<Symptom>,
+        pub insurance: Option<Insurance>,
+        pub findings: Vec<Finding>,
+        pub tests: Vec<Test>,
+        pub prescriptions: Vec<Prescription>,
+        pub bill: Option<Bill>,
         pub registration_done: bool,
         pub doctor_check_up_done: bool,
         pub medicine_done: bool,
         pub payment_done: bool,
+        pub can_pay: bool,
+        pub trace: Trace,
+    }
+
+    impl Patient {
+        /// Describes the visit step by step, and tells whether the patient was
+        /// charged, or what prevented it.
+        pub fn visit_summary(&self) -> String {
+            let mut summary = format!("Visit of {}:\n{}", self.name, self.trace);
+
+            let charge = if self.payment_done {
+                match &self.bill {
+                    Some(bill) => format!("Charged {}", bill),
+                    None => "Charged".to_string(),
+                }
+            } else {
+                match self.trace.steps().last() {
+                    Some(step) => match &step.status {
+                        Status::Stopped(result) => format!("Not charged: {}", result),
+                        Status::Rejected(error) => format!("Not charged: {}", error),
+                        Status::TimedOut => {
+                            format!("Not charged: {} timed out", step.department)
+                        }
+                        Status::Done | Status::Skipped => {
+                            "Not charged: no department takes payment".to_string()
+                        }
+                    },
+                    None => "Not charged: the patient was not seen".to_string(),
+                }
+            };
+
+            writeln!(summary, "{}", charge).unwrap();
+            summary
+        }
+    }
+
+    /// Departments seeing copies of a patient at the same time only add to
+    /// the visit, so whatever each of them added is kept.
+    impl Fork for Patient {
+        fn join(&mut self, fork_point: &Self, branch: Self) {
+            for finding in branch.findings {
+                if !self.findings.contains(&finding) {
+                    self.findings.push(finding);
+                }
+            }
+            for test in branch.tests {
+                if !self.tests.contains(&test) {
+                    self.tests.push(test);
+                }
+            }
+            for prescription in branch.prescriptions {
+                match self
+                    .prescriptions
+                    .iter_mut()
+                    .find(|own| own.medicine == prescription.medicine)
+                {
+                    Some(own) => own.dispensed |= prescription.dispensed,
+                    None => self.prescriptions.push(prescription),
+                }
+            }
+
+            if self.bill.is_none() {
+                self.bill = branch.bill;
+            }
+            self.registration_done |= branch.registration_done;
+            self.doctor_check_up_done |= branch.doctor_check_up_done;
+            self.medicine_done |= branch.medicine_done;
+            self.payment_done |= branch.payment_done;
+
+            self.trace.join(&fork_point.trace, branch.trace);
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use std::time::{Duration, SystemTime};
+
+        use super::{Finding, Medicine, Patient, Prescription, Test};
+        use crate::{handler::Fork, trace::Status};
+
+        fn step(patient: &mut Patient, department: &'static str) {
+            patient
+                .trace
+                .record(department, SystemTime::now(), Status::Done, Duration::ZERO);
+        }
+
+        fn departments(patient: &Patient) -> Vec<&'static str> {
+            patient
+                .trace
+                .steps()
+                .iter()
+                .map(|step| step.department)
+                .collect()
+        }
+
+        #[test]
+        fn joined_copies_keep_what_each_of_them_added() {
+            let mut patient = Patient {
+                findings: vec![Finding::SuspectedFracture],
+                prescriptions: vec![Prescription {
+                    medicine: Medicine::Ibuprofen,
+                    dispensed: false,
+                }],
+                ..Patient::default()
+            };
+            step(&mut patient, "doctor");
+            let fork_point = patient.clone();
+
+            let mut radiology = fork_point.clone();
+            radiology.tests.push(Test::XRay);
+            radiology.findings.push(Finding::SuspectedInfection);
+            step(&mut radiology, "radiology");
+
+            let mut lab = fork_point.clone();
+            lab.tests.push(Test::BloodTest);
+            lab.findings.push(Finding::SuspectedInfection);
+            lab.prescriptions[0].dispensed = true;
+            lab.medicine_done = true;
+            step(&mut lab, "lab");
+
+            patient.join(&fork_point, radiology);
+            patient.join(&fork_point, lab);
+
+            assert_eq!(
+                patient.findings,
+                [Finding::SuspectedFracture, Finding::SuspectedInfection]
+            );
+            assert_eq!(patient.tests, [Test::XRay, Test::BloodTest]);
+            assert_eq!(patient.prescriptions.len(), 1);
+            assert!(patient.prescriptions[0].dispensed);
+            assert!(patient.medicine_done);
+            assert!(!patient.payment_done);
+            // Steps before the fork are not repeated.
+            assert_eq!(departments(&patient), ["doctor", "radiology", "lab"]);
+        }
+    }
+}
+
+// Handlers
+mod department {
+    // mod cashier;
+    // mod doctor;
+    // mod lab;
+    // mod medical;
+    // mod radiology;
+    // mod reception;
+    pub use cashier::Cashier;
+    pub use doctor::Doctor;
+    pub use lab::Lab;
+    pub use medical::Medical;
+    pub use radiology::Radiology;
+    pub use reception::Reception;
+
+    use std::time::{Instant, SystemTime};
+
+    use crate::{
+        handler::{self, Handler},
+        patient::Patient,
+        trace::Status,
+    };
+
+    /// What a department decided about a patient.
+    pub type Outcome = handler::Outcome<String>;
+
+    /// A single role of objects that make up a hospital's chain.
+    ///
+    /// Every department is a `Handler` of patients, that records each of its
+    /// steps in the patient's trace, so it can be put into any `Link` or
+    /// `Pipeline`.
+    pub trait Department {
+        fn handle(&mut self, patient: &mut Patient) -> Outcome;
+        fn name(&self) -> &'static str;
+    }
+
+    impl<D: Department> Handler<Patient, String> for D {
+        fn handle(&mut self, patient: &mut Patient) -> Outcome {
+            let started_at = SystemTime::now();
+            let timer = Instant::now();
+            let outcome = Department::handle(self, patient);
+            patient.trace.record(
+                Department::name(self),
+                started_at,
+                Status::from(&outcome),
+                timer.elapsed(),
+            );
+            outcome
+        }
+
+        fn name(&self) -> &'static str {
+            Department::name(self)
+        }
+    }
+
+    mod cashier {
+        use super::{Department, Outcome, Patient};
+        use crate::patient::{Bill, Cents};
+
+        pub const CONSULTATION_FEE: Cents = 6000;
+
+        #[derive(Default)]
+        pub struct Cashier;
+
+        impl Cashier {
+            /// Prices the consultation, the tests taken and the dispensed
+            /// medicine, minus what the patient's insurance covers.
+            pub fn bill(patient: &Patient) -> Bill {
+                let tests: Cents = patient.tests.iter().map(|test| test.price()).sum();
+                let medicine: Cents = patient
+                    .prescriptions
+                    .iter()
+                    .filter(|prescription| prescription.dispensed)
+                    .map(|prescription| prescription.medicine.price())
+                    .sum();
+
+                let covered = match &patient.insurance {
+                    Some(insurance) => {
+                        CONSULTATION_FEE.saturating_sub(insurance.consultation_co_pay)
+                            + (tests + medicine) * Cents::from(insurance.coverage.min(100)) / 100
+                    }
+                    None => 0,
+                };
+
+                Bill {
+                    consultation: CONSULTATION_FEE,
+                    tests,
+                    medicine,
+                    covered,
+                }
+            }
+        }
+
+        impl Department for Cashier {
+            fn handle(&mut self, patient: &mut Patient) -> Outcome {
+                if patient.payment_done {
+                    println!("Payment done");
+                    return Outcome::Skip;
+                }
+
+                let bill = Cashier::bill(patient);
+                if bill.due() > 0 && !patient.can_pay {
+                    println!("Cashier refusing a patient {}", patient.name);
+                    return Outcome::Reject(format!("{} has no means of payment", patient.name));
+                }
+
+                println!("Cashier charging a patient {}: {}", patient.name, bill);
+                patient.bill = Some(bill);
+                patient.payment_done = true;
+
+                Outcome::Continue
+            }
+
+            fn name(&self) -> &'static str {
+                "cashier"
+            }
+        }
+
+        #[cfg(test)]
+        mod tests {
+            use super::{Cashier, Department, Outcome};
+            use crate::patient::{Insurance, Medicine, Patient, Prescription, Test};
+
+            /// Had a blood test, and was given cough syrup but not ibuprofen
+            /// yet.
+            fn patient(insurance: Option<Insurance>, can_pay: bool) -> Patient {
+                Patient {
+                    name: "Ann".into(),
+                    insurance,
+                    tests: vec![Test::BloodTest],
+                    prescriptions: vec![
+                        Prescription {
+                            medicine: Medicine::CoughSyrup,
+                            dispensed: true,
+                        },
+                        Prescription {
+                            medicine: Medicine::Ibuprofen,
+                            dispensed: false,
+                        },
+                    ],
+                    can_pay,
+                    ..Patient::default()
+                }
+            }
+
+            fn insurance(consultation_co_pay: u32, coverage: u8) -> Option<Insurance> {
+                Some(Insurance {
+                    provider: "Acme Health".into(),
+                    consultation_co_pay,
+                    coverage,
+                })
+            }
+
+            #[test]
+            fn uninsured_patients_pay_for_everything_dispensed() {
+                let bill = Cashier::bill(&patient(None, true));
+
+                assert_eq!(bill.consultation, 6000);
+                assert_eq!(bill.tests, 3800);
+                assert_eq!(bill.medicine, 890);
+                assert_eq!(bill.covered, 0);
+                assert_eq!(bill.due(), 10690);
+            }
+
+            #[test]
+            fn insured_patients_pay_the_co_pay_and_the_uncovered_share() {
+                let bill = Cashier::bill(&patient(insurance(1500, 80), true));
+
+                // 4500 of the consultation and 80% of 4690.
+                assert_eq!(bill.covered, 8252);
+                assert_eq!(bill.due(), 2438);
+                assert_eq!(
+                    bill.to_string(),
+                    "$24.38 due (consultation $60.00, tests $38.00, medicine $8.90, covered $82.52)"
+                );
+            }
+
+            #[test]
+            fn coverage_is_capped_at_the_price() {
+                let bill = Cashier::bill(&patient(insurance(0, 150), true));
+                assert_eq!(bill.covered, 10690);
+                assert_eq!(bill.due(), 0);
+
+                // A co-pay above the fee leaves the consultation uncovered.
+                let bill = Cashier::bill(&patient(insurance(8000, 0), true));
+                assert_eq!(bill.covered, 0);
+                assert_eq!(bill.due(), 10690);
+            }
+
+            #[test]
+            fn patients_with_nothing_to_pay_with_are_rejected() {
+                let mut patient = patient(insurance(1500, 80), false);
+
+                let outcome = Cashier.handle(&mut patient);
+
+                assert_eq!(
+                    outcome,
+                    Outcome::Reject("Ann has no means of payment".into())
+                );
+                assert!(!patient.payment_done);
+                assert!(patient.bill.is_none());
+            }
+
+            #[test]
+            fn fully_covered_patients_need_no_means_of_payment() {
+                let mut patient = patient(insurance(0, 100), false);
+
+                assert_eq!(Cashier.handle(&mut patient), Outcome::Continue);
+                assert!(patient.payment_done);
+                assert_eq!(patient.bill.as_ref().map(|bill| bill.due()), Some(0));
+
+                // Nobody is charged twice.
+                assert_eq!(Cashier.handle(&mut patient), Outcome::Skip);
+            }
+        }
+    }
+
+    mod doctor {
+        use super::{Department, Outcome, Patient};
+        use crate::patient::{Finding, Medicine, Prescription, Priority};
+
+        #[derive(Default)]
+        pub struct Doctor;
+
+        impl Department for Doctor {
+            fn handle(&mut self, patient: &mut Patient) -> Outcome {
+                if patient.doctor_check_up_done {
+                    println!("A doctor checkup is already done");
+                    return Outcome::Skip;
+                } else if patient.priority == Priority::Emergency {
+                    println!(
+                        "Doctor sending a patient {} to the emergency room",
+                        patient.name
+                    );
+                    patient.doctor_check_up_done = true;
+                    return Outcome::Stop(format!(
+                        "{} is taken to the emergency room",
+                        patient.name
+                    ));
+                } else {
+                    println!("Doctor checking a patient {}", patient.name);
+                    patient.doctor_check_up_done = true;
+
+                    for &symptom in &patient.symptoms {
+                        let medicine = Medicine::for_symptom(symptom);
+                        if patient
+                            .prescriptions
+                            .iter()
+                            .all(|prescription| prescription.medicine != medicine)
+                        {
+                            println!("Doctor prescribing {:?} for {:?}", medicine, symptom);
+                            patient.prescriptions.push(Prescription {
+                                medicine,
+                                dispensed: false,
+                            });
+                        }
+
+                        if let Some(finding) = Finding::for_symptom(symptom) {
+                            if !patient.findings.contains(&finding) {
+                                println!("Doctor noting {:?} for {:?}", finding, symptom);
+                                patient.findings.push(finding);
+                            }
+                        }
+                    }
+                }
+
+                Outcome::Continue
+            }
+
+            fn name(&self) -> &'static str {
+                "doctor"
+            }
+        }
+    }
+
+    mod lab {
+        use super::{Department, Outcome, Patient};
+        use crate::patient::{Finding, Test};
+
+        #[derive(Default)]
+        pub struct Lab;
+
+        impl Department for Lab {
+            fn handle(&mut self, patient: &mut Patient) -> Outcome {
+                if !patient.findings.contains(&Finding::SuspectedInfection) {
+                    println!("No blood test is needed for a patient {}", patient.name);
+                    return Outcome::Skip;
+                } else if patient.tests.contains(&Test::BloodTest) {
+                    println!("A blood test is already done");
+                    return Outcome::Skip;
+                } else {
+                    println!("Lab taking a blood test from a patient {}", patient.name);
+                    patient.tests.push(Test::BloodTest);
+                }
+
+                Outcome::Continue
+            }
+
+            fn name(&self) -> &'static str {
+                "lab"
+            }
+        }
+    }
+
+    mod medical {
+        use super::{Department, Outcome, Patient};
+
+        #[derive(Default)]
+        pub struct Medical;
+
+        impl Department for Medical {
+            fn handle(&mut self, patient: &mut Patient) -> Outcome {
+                if patient.medicine_done {
+                    println!("Medicine is already given to a patient");
+                    return Outcome::Skip;
+                } else if patient.prescriptions.is_empty() {
+                    println!("No medicine is prescribed to a patient {}", patient.name);
+                    return Outcome::Skip;
+                } else {
+                    for prescription in &mut patient.prescriptions {
+                        println!(
+                            "Medical giving {:?} to a patient {}",
+                            prescription.medicine, patient.name
+                        );
+                        prescription.dispensed = true;
+                    }
+                    patient.medicine_done = true;
+                }
+
+                Outcome::Continue
+            }
+
+            fn name(&self) -> &'static str {
+                "medical"
+            }
+        }
+    }
+
+    mod radiology {
+        use super::{Department, Outcome, Patient};
+        use crate::patient::{Finding, Test};
+
+        #[derive(Default)]
+        pub struct Radiology;
+
+        impl Department for Radiology {
+            fn handle(&mut self, patient: &mut Patient) -> Outcome {
+                if !patient.findings.contains(&Finding::SuspectedFracture) {
+                    println!("No X-ray is needed for a patient {}", patient.name);
+                    return Outcome::Skip;
+                } else if patient.tests.contains(&Test::XRay) {
+                    println!("An X-ray is already taken");
+                    return Outcome::Skip;
+                } else {
+                    println!("Radiology taking an X-ray of a patient {}", patient.name);
+                    patient.tests.push(Test::XRay);
+                }
+
+                Outcome::Continue
+            }
+
+            fn name(&self) -> &'static str {
+                "radiology"
+            }
+        }
+    }
+
+    mod reception {
+        use super::{Department, Outcome, Patient};
+
+        #[derive(Default)]
+        pub struct Reception;
+
+        impl Department for Reception {
+            fn handle(&mut self, patient: &mut Patient) -> Outcome {
+                if patient.registration_done {
+                    println!("Patient registration is already done");
+                    return Outcome::Skip;
+                } else {
+                    println!("Reception registering a patient {}", patient.name);
+                    patient.registration_done = true;
+                }
+
+                Outcome::Continue
+            }
+
+            fn name(&self) -> &'static str {
+                "reception"
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::{Cashier, Doctor, Medical, Outcome, Reception};
+        use crate::{
+            handler::Link,
+            patient::{Patient, Priority},
+            trace::Status,
+        };
+
+        fn front_desk() -> Link<Patient, String> {
+            Link::new(Reception)
+                .then(Doctor)
+                .then(Medical)
+                .then(Cashier)
+        }
+
+        fn patient(name: &str, priority: Priority, can_pay: bool) -> Patient {
+            Patient {
+                name: name.into(),
+                priority,
+                can_pay,
+                ..Patient::default()
+            }
+        }
+
+        fn statuses(patient: &Patient) -> Vec<(&'static str, Status)> {
+            patient
+                .trace
+                .steps()
+                .iter()
+                .map(|step| (step.department, step.status.clone()))
+                .collect()
+        }
+
+        #[test]
+        fn skipped_departments_pass_the_patient_on() {
+            let mut patient = patient("John", Priority::Standard, true);
+
+            assert_eq!(front_desk().execute(&mut patient), Outcome::Continue);
+            assert_eq!(
+                statuses(&patient),
+                [
+                    ("reception", Status::Done),
+                    ("doctor", Status::Done),
+                    ("medical", Status::Skipped),
+                    ("cashier", Status::Done),
+                ]
+            );
+            assert!(patient.payment_done);
+        }
+
+        #[test]
+        fn emergency_stops_the_chain_at_the_doctor() {
+            let mut patient = patient("Jack", Priority::Emergency, true);
+
+            let outcome = front_desk().execute(&mut patient);
+
+            let result = "Jack is taken to the emergency room".to_string();
+            assert_eq!(outcome, Outcome::Stop(result.clone()));
+            assert_eq!(
+                statuses(&patient),
+                [
+                    ("reception", Status::Done),
+                    ("doctor", Status::Stopped(result)),
+                ]
+            );
+            assert!(!patient.payment_done);
+            assert!(patient
+                .visit_summary()
+                .ends_with("Not charged: Jack is taken to the emergency room\n"));
+        }
+
+        #[test]
+        fn patient_who_cannot_pay_is_rejected_at_the_cashier() {
+            let mut patient = patient("Jane", Priority::Standard, false);
+
+            let outcome = front_desk().execute(&mut patient);
+
+            let error = "Jane has no means of payment".to_string();
+            assert_eq!(outcome, Outcome::Reject(error.clone()));
+            assert_eq!(statuses(&patient).len(), 4);
+            assert_eq!(
+                statuses(&patient).last(),
+                Some(&("cashier", Status::Rejected(error)))
+            );
+            assert!(!patient.payment_done);
+        }
+
+        #[test]
+        fn departments_done_before_are_skipped_on_a_second_visit() {
+            let mut chain = front_desk();
+            let mut patient = patient("John", Priority::Standard, true);
+            chain.execute(&mut patient);
+
+            assert_eq!(chain.execute(&mut patient), Outcome::Continue);
+
+            let statuses = statuses(&patient);
+            assert_eq!(statuses.len(), 8);
+            assert!(statuses[4..]
+                .iter()
+                .all(|(_, status)| *status == Status::Skipped));
+            assert!(patient.visit_summary().ends_with(
+                "Charged $60.00 due (consultation $60.00, tests $0.00, medicine $0.00, covered $0.00)\n"
+            ));
+        }
+    }
+}
+
+// Asynchronous handlers
+mod async_department {
+    // mod chain;
+    // mod laboratory;
+    // mod payment_gateway;
+    pub use chain::AsyncChain;
+    pub use laboratory::Laboratory;
+    pub use payment_gateway::PaymentGateway;
+
+    use std::{future::Future, pin::Pin};
+
+    use crate::{
+        department::{Department, Outcome},
+        patient::Patient,
+    };
+
+    pub type HandleFuture<'a> = Pin<Box<dyn Future<Output = Outcome> + 'a>>;
+
+    /// A department that may wait on something, e.g. lab results or
+    /// a payment processor, while handling a patient.
+    pub trait AsyncDepartment {
+        fn handle<'a>(&'a mut self, patient: &'a mut Patient) -> HandleFuture<'a>;
+        fn name(&self) -> &'static str;
+    }
+
+    /// Any department can take part in an asynchronous chain, it just never
+    /// waits.
+    impl<D: Department> AsyncDepartment for D {
+        fn handle<'a>(&'a mut self, patient: &'a mut Patient) -> HandleFuture<'a> {
+            let outcome = Department::handle(self, patient);
+            Box::pin(async { outcome })
+        }
+
+        fn name(&self) -> &'static str {
+            Department::name(self)
+        }
+    }
+
+    mod chain {
+        use std::time::{Duration, Instant, SystemTime};
+
+        use super::AsyncDepartment;
+        use crate::{department::Outcome, patient::Patient, trace::Status};
+
+        pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
+        struct Step {
+            department: Box<dyn AsyncDepartment>,
+            timeout: Duration,
+        }
+
+        /// Runs asynchronous departments one after another, giving each of
+        /// them a limited time to handle a patient.
+        ///
+        /// Every step is recorded in the patient's trace, a step that runs out
+        /// of time rejects the patient.
+        #[derive(Default)]
+        pub struct AsyncChain {
+            steps: Vec<Step>,
+        }
+
+        impl AsyncChain {
+            pub fn push(&mut self, department: impl AsyncDepartment + 'static) {
+                self.push_with_timeout(department, DEFAULT_TIMEOUT);
+            }
+
+            pub fn push_with_timeout(
+                &mut self,
+                department: impl AsyncDepartment + 'static,
+                timeout: Duration,
+            ) {
+                self.steps.push(Step {
+                    department: Box::new(department),
+                    timeout,
+                });
+            }
+
+            /// Must be run within a Tokio runtime with the time driver enabled.
+            pub async fn execute(&mut self, patient: &mut Patient) -> Outcome {
+                for step in &mut self.steps {
+                    let name = step.department.name();
+                    let started_at = SystemTime::now();
+                    let timer = Instant::now();
+                    let result =
+                        tokio::time::timeout(step.timeout, step.department.handle(patient)).await;
+
+                    let outcome = match result {
+                        Ok(outcome) => {
+                            patient.trace.record(
+                                name,
+                                started_at,
+                                Status::from(&outcome),
+                                timer.elapsed(),
+                            );
+                            outcome
+                        }
+                        Err(_) => {
+                            patient.trace.record(
+                                name,
+                                started_at,
+                                Status::TimedOut,
+                                timer.elapsed(),
+                            );
+                            Outcome::Reject(format!(
+                                "{} did not respond within {:?}",
+                                name, step.timeout
+                            ))
+                        }
+                    };
+
+                    if !outcome.passes() {
+                        return outcome;
+                    }
+                }
+
+                Outcome::Continue
+            }
+        }
+
+        #[cfg(test)]
+        mod tests {
+            use std::time::Duration;
+
+            use super::AsyncChain;
+            use crate::{
+                async_department::{Laboratory, PaymentGateway},
+                department::Outcome,
+                patient::Patient,
+                trace::Status,
+            };
+
+            fn patient() -> Patient {
+                Patient {
+                    name: "Jenny".into(),
+                    can_pay: true,
+                    ..Patient::default()
+                }
+            }
+
+            fn statuses(patient: &Patient) -> Vec<(&'static str, Status)> {
+                patient
+                    .trace
+                    .steps()
+                    .iter()
+                    .map(|step| (step.department, step.status.clone()))
+                    .collect()
+            }
+
+            #[tokio::test(flavor = "current_thread", start_paused = true)]
+            async fn steps_within_their_time_pass() {
+                let mut chain = AsyncChain::default();
+                chain.push_with_timeout(
+                    Laboratory::new(Duration::from_millis(50)),
+                    Duration::from_millis(200),
+                );
+                chain.push_with_timeout(
+                    PaymentGateway::new(Duration::from_millis(100)),
+                    Duration::from_millis(200),
+                );
+                let mut patient = patient();
+
+                let outcome = chain.execute(&mut patient).await;
+
+                assert_eq!(outcome, Outcome::Continue);
+                assert!(patient.payment_done);
+                assert_eq!(
+                    statuses(&patient),
+                    [
+                        ("laboratory", Status::Done),
+                        ("payment_gateway", Status::Done)
+                    ]
+                );
+            }
+
+            #[tokio::test(flavor = "current_thread", start_paused = true)]
+            async fn step_out_of_time_rejects_the_patient() {
+                let mut chain = AsyncChain::default();
+                chain.push_with_timeout(
+                    PaymentGateway::new(Duration::from_millis(300)),
+                    Duration::from_millis(100),
+                );
+                chain.push(Laboratory::new(Duration::from_millis(50)));
+                let mut patient = patient();
+
+                let outcome = chain.execute(&mut patient).await;
+
+                assert_eq!(
+                    outcome,
+                    Outcome::Reject("payment_gateway did not respond within 100ms".into())
+                );
+                assert!(!patient.payment_done);
+                assert_eq!(statuses(&patient), [("payment_gateway", Status::TimedOut)]);
+            }
+        }
+    }
+
+    mod laboratory {
+        use std::time::Duration;
+
+        use super::{AsyncDepartment, HandleFuture};
+        use crate::{department::Outcome, patient::Patient};
+
+        /// Waits for test results to come back.
+        pub struct Laboratory {
+            turnaround: Duration,
+        }
+
+        impl Laboratory {
+            pub fn new(turnaround: Duration) -> Self {
+                Self { turnaround }
+            }
+        }
+
+        impl AsyncDepartment for Laboratory {
+            fn handle<'a>(&'a mut self, patient: &'a mut Patient) -> HandleFuture<'a> {
+                Box::pin(async move {
+                    println!("Laboratory testing a patient {}", patient.name);
+                    tokio::time::sleep(self.turnaround).await;
+                    println!("Laboratory results are ready for {}", patient.name);
+                    Outcome::Continue
+                })
+            }
+
+            fn name(&self) -> &'static str {
+                "laboratory"
+            }
+        }
+    }
+
+    mod payment_gateway {
+        use std::time::Duration;
+
+        use super::{AsyncDepartment, HandleFuture};
+        use crate::{department::Outcome, patient::Patient};
+
+        /// Charges a patient through an external payment processor.
+        pub struct PaymentGateway {
+            latency: Duration,
+        }
+
+        impl PaymentGateway {
+            pub fn new(latency: Duration) -> Self {
+                Self { latency }
+            }
+        }
+
+        impl AsyncDepartment for PaymentGateway {
+            fn handle<'a>(&'a mut self, patient: &'a mut Patient) -> HandleFuture<'a> {
+                Box::pin(async move {
+                    if patient.payment_done {
+                        println!("Payment done");
+                        return Outcome::Skip;
+                    }
+
+                    println!("Payment gateway charging a patient {}", patient.name);
+                    tokio::time::sleep(self.latency).await;
+
+                    if patient.can_pay {
+                        patient.payment_done = true;
+                        Outcome::Continue
+                    } else {
+                        Outcome::Reject(format!("{}'s payment was declined", patient.name))
+                    }
+                })
+            }
+
+            fn name(&self) -> &'static str {
+                "payment_gateway"
+            }
+        }
     }
 }
 
-// Handlers
-mod department {
-    // mod cashier;
-    // mod doctor;
-    // mod medical;
-    // mod reception;
-    pub use cashier::Cashier;
-    pub use doctor::Doctor;
-    pub use medical::Medical;
-    pub use reception::Reception;
+// Visit history
+mod trace {
+    use std::{
+        fmt,
+        time::{Duration, SystemTime, UNIX_EPOCH},
+    };
 
-    use crate::patient::Patient;
+    use serde::Serialize;
 
-    /// A single role of objects that make up a chain.
-    /// A typical trait implementation must have `handle` and `next` methods,
-    /// while `execute` is implemented by default and contains a proper chaining
-    /// logic.
-    pub trait Department {
-        fn execute(&mut self, patient: &mut Patient) {
-            self.handle(patient);
+    use crate::{department::Outcome, handler::Fork};
+
+    /// How a department's step ended.
+    #[derive(Clone, Debug, PartialEq, Serialize)]
+    #[serde(tag = "status", content = "reason", rename_all = "snake_case")]
+    pub enum Status {
+        /// The department did its job.
+        Done,
+        /// The department had nothing to do.
+        Skipped,
+        Stopped(String),
+        Rejected(String),
+        TimedOut,
+    }
 
-            if let Some(next) = &mut self.next() {
-                next.execute(patient);
+    impl From<&Outcome> for Status {
+        fn from(outcome: &Outcome) -> Self {
+            match outcome {
+                Outcome::Continue => Status::Done,
+                Outcome::Skip => Status::Skipped,
+                Outcome::Stop(result) => Status::Stopped(result.clone()),
+                Outcome::Reject(error) => Status::Rejected(error.clone()),
             }
         }
+    }
 
-        fn handle(&mut self, patient: &mut Patient);
-        fn next(&mut self) -> &mut Option<Box<dyn Department>>;
+    #[derive(Clone, Serialize)]
+    pub struct Step {
+        pub department: &'static str,
+        #[serde(rename = "timestamp_ms", serialize_with = "unix_millis")]
+        pub started_at: SystemTime,
+        #[serde(flatten)]
+        pub status: Status,
+        #[serde(rename = "duration_us", serialize_with = "micros")]
+        pub duration: Duration,
     }
 
-    /// Helps to wrap an object into a boxed type.
-    pub(self) fn into_next(
-        department: impl Department + Sized + 'static,
-    ) -> Option<Box<dyn Department>> {
-        Some(Box::new(department))
+    fn unix_millis<S: serde::Serializer>(
+        time: &SystemTime,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let millis = time
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        serializer.serialize_u64(millis as u64)
     }
 
-    mod cashier {
-        use super::{Department, Patient};
+    fn micros<S: serde::Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u64(duration.as_micros() as u64)
+    }
 
-        #[derive(Default)]
-        pub struct Cashier {
-            next: Option<Box<dyn Department>>,
+    /// Steps a patient went through, in order, across all visits.
+    #[derive(Clone, Default, Serialize)]
+    #[serde(transparent)]
+    pub struct Trace {
+        steps: Vec<Step>,
+    }
+
+    impl Trace {
+        pub fn record(
+            &mut self,
+            department: &'static str,
+            started_at: SystemTime,
+            status: Status,
+            duration: Duration,
+        ) {
+            self.steps.push(Step {
+                department,
+                started_at,
+                status,
+                duration,
+            });
         }
 
-        impl Department for Cashier {
-            fn handle(&mut self, patient: &mut Patient) {
-                if patient.payment_done {
-                    println!("Payment done");
-                } else {
-                    println!("Cashier getting money from a patient {}", patient.name);
-                    patient.payment_done = true;
+        pub fn steps(&self) -> &[Step] {
+            &self.steps
+        }
+
+        pub fn to_json(&self) -> String {
+            serde_json::to_string_pretty(self).expect("a trace is serializable")
+        }
+    }
+
+    /// Steps taken side by side are listed one branch after another.
+    impl Fork for Trace {
+        fn join(&mut self, fork_point: &Self, branch: Self) {
+            self.steps
+                .extend(branch.steps.into_iter().skip(fork_point.steps.len()));
+        }
+    }
+
+    /// Lists steps with their time since the first one.
+    impl fmt::Display for Trace {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            let start = self.steps.first().map(|step| step.started_at);
+
+            for step in &self.steps {
+                let offset = start
+                    .and_then(|start| step.started_at.duration_since(start).ok())
+                    .unwrap_or_default();
+
+                write!(
+                    f,
+                    "+{:>8.3}s  {:<16} {:>10.1?}  ",
+                    offset.as_secs_f64(),
+                    step.department,
+                    step.duration
+                )?;
+
+                match &step.status {
+                    Status::Done => writeln!(f, "done")?,
+                    Status::Skipped => writeln!(f, "skipped, nothing to do")?,
+                    Status::Stopped(result) => writeln!(f, "stopped: {}", result)?,
+                    Status::Rejected(error) => writeln!(f, "rejected: {}", error)?,
+                    Status::TimedOut => writeln!(f, "timed out")?,
                 }
             }
 
-            fn next(&mut self) -> &mut Option<Box<dyn Department>> {
-                &mut self.next
-            }
+            Ok(())
         }
     }
 
-    mod doctor {
-        use super::{into_next, Department, Patient};
+    #[cfg(test)]
+    mod tests {
+        use std::time::{Duration, UNIX_EPOCH};
+
+        use super::{Status, Trace};
+        use crate::department::Outcome;
+
+        /// A visit started at 2023-11-14 22:13:20.125 UTC.
+        fn trace() -> Trace {
+            let start = UNIX_EPOCH + Duration::from_millis(1_700_000_000_125);
+            let mut trace = Trace::default();
+            let steps = [
+                ("reception", Status::Done, 0, 120),
+                ("medical", Status::Skipped, 1, 15),
+                ("doctor", Status::Stopped("sent home".into()), 2, 2_500),
+                ("cashier", Status::Rejected("declined".into()), 2_003, 999),
+                ("payment_gateway", Status::TimedOut, 2_500, 100_000),
+            ];
+            for (department, status, offset_ms, duration_us) in steps {
+                trace.record(
+                    department,
+                    start + Duration::from_millis(offset_ms),
+                    status,
+                    Duration::from_micros(duration_us),
+                );
+            }
+            trace
+        }
 
-        pub struct Doctor {
-            next: Option<Box<dyn Department>>,
+        #[test]
+        fn outcomes_are_recorded_as_statuses() {
+            let outcomes = [
+                Outcome::Continue,
+                Outcome::Skip,
+                Outcome::Stop("sent home".into()),
+                Outcome::Reject("declined".into()),
+            ];
+            let statuses = outcomes.iter().map(Status::from).collect::<Vec<_>>();
+
+            assert_eq!(
+                statuses,
+                [
+                    Status::Done,
+                    Status::Skipped,
+                    Status::Stopped("sent home".into()),
+                    Status::Rejected("declined".into()),
+                ]
+            );
         }
 
-        impl Doctor {
-            pub fn new(next: impl Department + 'static) -> Self {
-                Self {
-                    next: into_next(next),
+        #[test]
+        fn trace_is_exported_as_json() {
+            let expected = r#"[
+  {
+    "department": "reception",
+    "timestamp_ms": 1700000000125,
+    "status": "done",
+    "duration_us": 120
+  },
+  {
+    "department": "medical",
+    "timestamp_ms": 1700000000126,
+    "status": "skipped",
+    "duration_us": 15
+  },
+  {
+    "department": "doctor",
+    "timestamp_ms": 1700000000127,
+    "status": "stopped",
+    "reason": "sent home",
+    "duration_us": 2500
+  },
+  {
+    "department": "cashier",
+    "timestamp_ms": 1700000002128,
+    "status": "rejected",
+    "reason": "declined",
+    "duration_us": 999
+  },
+  {
+    "department": "payment_gateway",
+    "timestamp_ms": 1700000002625,
+    "status": "timed_out",
+    "duration_us": 100000
+  }
+]"#;
+
+            assert_eq!(trace().to_json(), expected);
+            assert_eq!(Trace::default().to_json(), "[]");
+        }
+
+        #[test]
+        fn trace_is_listed_with_offsets_from_the_first_step() {
+            let listed = trace().to_string();
+            let lines: Vec<&str> = listed.lines().collect();
+
+            assert_eq!(lines.len(), 5);
+            assert!(lines[0].starts_with("+   0.000s  reception"));
+            assert!(lines[1].ends_with("skipped, nothing to do"));
+            assert!(lines[2].ends_with("stopped: sent home"));
+            assert!(lines[3].starts_with("+   2.003s  cashier"));
+            assert!(lines[4].ends_with("timed out"));
+        }
+    }
+}
+
+// Chain assembly
+mod chain {
+    use std::{fmt, fs, io, path::Path};
+
+    use crate::{
+        department::{Cashier, Doctor, Lab, Medical, Radiology, Reception},
+        handler::{Handler, Pipeline},
+        patient::Patient,
+    };
+
+    /// A hospital's departments in the order a patient visits them.
+    pub type Chain = Pipeline<Patient, String>;
+
+    #[derive(Debug)]
+    pub enum ConfigError {
+        Io(io::Error),
+        UnknownDepartment { line: usize, name: String },
+    }
+
+    impl fmt::Display for ConfigError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                ConfigError::Io(err) => write!(f, "cannot read the chain config: {}", err),
+                ConfigError::UnknownDepartment { line, name } => {
+                    write!(f, "line {}: unknown department `{}`", line, name)
                 }
             }
         }
+    }
 
-        impl Department for Doctor {
-            fn handle(&mut self, patient: &mut Patient) {
-                if patient.doctor_check_up_done {
-                    println!("A doctor checkup is already done");
-                } else {
-                    println!("Doctor checking a patient {}", patient.name);
-                    patient.doctor_check_up_done = true;
+    impl std::error::Error for ConfigError {}
+
+    impl From<io::Error> for ConfigError {
+        fn from(err: io::Error) -> Self {
+            ConfigError::Io(err)
+        }
+    }
+
+    /// Creates a department by its type name, as used in config files.
+    pub fn department(name: &str) -> Option<Box<dyn Handler<Patient, String>>> {
+        match name {
+            "reception" => Some(Box::new(Reception)),
+            "doctor" => Some(Box::new(Doctor)),
+            "radiology" => Some(Box::new(Radiology)),
+            "lab" => Some(Box::new(Lab)),
+            "medical" => Some(Box::new(Medical)),
+            "cashier" => Some(Box::new(Cashier)),
+            _ => None,
+        }
+    }
+
+    impl Chain {
+        pub fn load(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+            Self::from_config(&fs::read_to_string(path)?)
+        }
+
+        /// Builds a chain from department type names, one per line.
+        /// Empty lines and lines starting with `#` are ignored.
+        pub fn from_config(config: &str) -> Result<Self, ConfigError> {
+            let mut departments = vec![];
+
+            for (number, line) in config.lines().enumerate() {
+                let name = line.trim();
+                if name.is_empty() || name.starts_with('#') {
+                    continue;
                 }
+
+                departments.push(department(name).ok_or_else(|| {
+                    ConfigError::UnknownDepartment {
+                        line: number + 1,
+                        name: name.into(),
+                    }
+                })?);
             }
 
-            fn next(&mut self) -> &mut Option<Box<dyn Department>> {
-                &mut self.next
+            Ok(Self::new(departments))
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use std::fs;
+
+        use super::{Chain, ConfigError};
+        use crate::department::{Cashier, Doctor, Medical, Reception};
+
+        fn chain() -> Chain {
+            Chain::new(vec![
+                Box::new(Reception),
+                Box::new(Doctor),
+                Box::new(Cashier),
+            ])
+        }
+
+        #[test]
+        fn departments_are_inserted_at_a_position_or_at_the_end() {
+            let mut chain = chain();
+
+            chain.insert(0, Medical);
+            assert_eq!(chain.names(), ["medical", "reception", "doctor", "cashier"]);
+            chain.insert(4, Medical);
+            chain.insert(100, Doctor);
+            assert_eq!(
+                chain.names(),
+                [
+                    "medical",
+                    "reception",
+                    "doctor",
+                    "cashier",
+                    "medical",
+                    "doctor"
+                ]
+            );
+        }
+
+        #[test]
+        fn first_department_by_name_is_removed() {
+            let mut chain = chain();
+            chain.push(Doctor);
+
+            assert_eq!(
+                chain.remove("doctor").map(|doctor| doctor.name()),
+                Some("doctor")
+            );
+            assert_eq!(chain.names(), ["reception", "cashier", "doctor"]);
+            assert!(chain.remove("surgery").is_none());
+            assert_eq!(chain.names(), ["reception", "cashier", "doctor"]);
+        }
+
+        #[test]
+        fn departments_are_moved_within_the_chain() {
+            let mut chain = chain();
+
+            assert!(chain.move_to("cashier", 0));
+            assert_eq!(chain.names(), ["cashier", "reception", "doctor"]);
+            assert!(chain.move_to("cashier", 100));
+            assert_eq!(chain.names(), ["reception", "doctor", "cashier"]);
+            assert!(!chain.move_to("surgery", 0));
+            assert_eq!(chain.names(), ["reception", "doctor", "cashier"]);
+        }
+
+        #[test]
+        fn config_skips_comments_and_blank_lines() {
+            let config = "# Front desk\n\n  reception  \n\t\n# Then\ndoctor\n  # indented\ncashier";
+
+            let chain = Chain::from_config(config).unwrap();
+
+            assert_eq!(chain.names(), ["reception", "doctor", "cashier"]);
+            assert!(Chain::from_config("# nothing\n\n")
+                .unwrap()
+                .names()
+                .is_empty());
+        }
+
+        #[test]
+        fn unknown_departments_are_reported_with_their_line() {
+            let err = Chain::from_config("# Surgery\nreception\n\nsurgery\ncashier")
+                .err()
+                .unwrap();
+
+            assert!(matches!(
+                &err,
+                ConfigError::UnknownDepartment { line: 4, name } if name == "surgery"
+            ));
+            assert_eq!(err.to_string(), "line 4: unknown department `surgery`");
+            // Names are not guessed at.
+            assert!(Chain::from_config("Reception").is_err());
+        }
+
+        #[test]
+        fn missing_config_is_an_io_error() {
+            assert!(matches!(
+                Chain::load("hospitals/missing.chain"),
+                Err(ConfigError::Io(_))
+            ));
+        }
+
+        #[test]
+        fn shipped_hospitals_load_in_order() {
+            let hospitals = concat!(env!("CARGO_MANIFEST_DIR"), "/hospitals");
+            let expected = [
+                (
+                    "general.chain",
+                    vec!["reception", "doctor", "medical", "cashier"],
+                ),
+                ("walk_in_clinic.chain", vec!["cashier", "doctor"]),
+            ];
+
+            let mut files: Vec<String> = fs::read_dir(hospitals)
+                .unwrap()
+                .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+                .collect();
+            files.sort();
+            let shipped: Vec<&str> = expected.iter().map(|(file, _)| *file).collect();
+            assert_eq!(files, shipped);
+
+            for (file, departments) in expected {
+                let chain = Chain::load(format!("{}/{}", hospitals, file)).unwrap();
+                assert_eq!(chain.names(), departments, "{}", file);
             }
         }
     }
+}
 
-    mod medical {
-        use super::{into_next, Department, Patient};
+// Front door
+mod hospital {
+    use std::{
+        cmp::{Ordering, Reverse},
+        collections::BinaryHeap,
+    };
+
+    use crate::{
+        chain::Chain,
+        department::Outcome,
+        patient::{Patient, Priority},
+    };
 
-        pub struct Medical {
-            next: Option<Box<dyn Department>>,
+    /// A patient waiting to be seen, the most urgent first and in order of
+    /// arrival among equally urgent ones.
+    struct Waiting {
+        arrival: Reverse<u64>,
+        patient: Patient,
+    }
+
+    impl Waiting {
+        fn key(&self) -> (Priority, Reverse<u64>) {
+            (self.patient.priority, self.arrival)
         }
+    }
 
-        impl Medical {
-            pub fn new(next: impl Department + 'static) -> Self {
-                Self {
-                    next: into_next(next),
-                }
+    impl PartialEq for Waiting {
+        fn eq(&self, other: &Self) -> bool {
+            self.key() == other.key()
+        }
+    }
+
+    impl Eq for Waiting {}
+
+    impl PartialOrd for Waiting {
+        fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+            Some(self.cmp(other))
+        }
+    }
+
+    impl Ord for Waiting {
+        fn cmp(&self, other: &Self) -> Ordering {
+            self.key().cmp(&other.key())
+        }
+    }
+
+    /// Queues arriving patients by triage priority and sends each one
+    /// through the department chain.
+    pub struct Hospital {
+        chain: Chain,
+        queue: BinaryHeap<Waiting>,
+        arrivals: u64,
+    }
+
+    impl Hospital {
+        pub fn new(chain: Chain) -> Self {
+            Self {
+                chain,
+                queue: BinaryHeap::new(),
+                arrivals: 0,
             }
         }
 
-        impl Department for Medical {
-            fn handle(&mut self, patient: &mut Patient) {
-                if patient.medicine_done {
-                    println!("Medicine is already given to a patient");
-                } else {
-                    println!("Medical giving medicine to a patient {}", patient.name);
-                    patient.medicine_done = true;
-                }
+        pub fn admit(&mut self, patient: Patient) {
+            self.queue.push(Waiting {
+                arrival: Reverse(self.arrivals),
+                patient,
+            });
+            self.arrivals += 1;
+        }
+
+        pub fn waiting(&self) -> usize {
+            self.queue.len()
+        }
+
+        /// Handles the most urgent waiting patient.
+        pub fn treat_next(&mut self) -> Option<(Patient, Outcome)> {
+            let Waiting { mut patient, .. } = self.queue.pop()?;
+            let outcome = self.chain.execute(&mut patient);
+            Some((patient, outcome))
+        }
+
+        /// Handles everyone in the queue, in order of priority.
+        pub fn treat_all(&mut self) -> Vec<(Patient, Outcome)> {
+            std::iter::from_fn(|| self.treat_next()).collect()
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::Hospital;
+        use crate::{
+            chain::Chain,
+            department::{Outcome, Reception},
+            patient::{Patient, Priority},
+        };
+
+        fn patient(name: &str, priority: Priority) -> Patient {
+            Patient {
+                name: name.into(),
+                priority,
+                ..Patient::default()
             }
+        }
+
+        #[test]
+        fn most_urgent_patients_are_treated_first_then_in_order_of_arrival() {
+            let mut hospital = Hospital::new(Chain::default());
+            hospital.admit(patient("Ann", Priority::NonUrgent));
+            hospital.admit(patient("Bob", Priority::Standard));
+            hospital.admit(patient("Cid", Priority::Urgent));
+            hospital.admit(patient("Dee", Priority::Standard));
+            hospital.admit(patient("Eve", Priority::Emergency));
+            hospital.admit(patient("Fay", Priority::Urgent));
+            assert_eq!(hospital.waiting(), 6);
+
+            let names: Vec<String> = hospital
+                .treat_all()
+                .into_iter()
+                .map(|(patient, _)| patient.name)
+                .collect();
+
+            assert_eq!(names, ["Eve", "Cid", "Fay", "Bob", "Dee", "Ann"]);
+            assert_eq!(hospital.waiting(), 0);
+        }
+
+        #[test]
+        fn patients_admitted_later_wait_behind_equally_urgent_ones() {
+            let mut hospital = Hospital::new(Chain::new(vec![Box::new(Reception)]));
+            hospital.admit(patient("Ann", Priority::Standard));
+            hospital.admit(patient("Bob", Priority::Standard));
+
+            let (first, outcome) = hospital.treat_next().unwrap();
+            assert_eq!(first.name, "Ann");
+            assert_eq!(outcome, Outcome::Continue);
+            assert!(first.registration_done);
+
+            hospital.admit(patient("Cid", Priority::Standard));
+            hospital.admit(patient("Dee", Priority::Urgent));
+            let names: Vec<String> = hospital
+                .treat_all()
+                .into_iter()
+                .map(|(patient, _)| patient.name)
+                .collect();
+            assert_eq!(names, ["Dee", "Bob", "Cid"]);
+            assert!(hospital.treat_next().is_none());
+        }
+    }
+}
+
+// Another service on the same machinery
+mod approval {
+    use crate::handler::{Handler, Outcome};
+
+    pub struct Expense {
+        pub description: String,
+        pub amount: u32,
+    }
+
+    #[derive(Debug, PartialEq)]
+    pub enum Decision {
+        Approved { by: &'static str },
+        Denied { by: &'static str, reason: String },
+    }
+
+    /// Approves expenses up to a limit, and leaves bigger ones to the next
+    /// approver in the chain.
+    pub struct Approver {
+        pub role: &'static str,
+        pub limit: u32,
+    }
 
-            fn next(&mut self) -> &mut Option<Box<dyn Department>> {
-                &mut self.next
+    impl Handler<Expense, Decision> for Approver {
+        fn handle(&mut self, expense: &mut Expense) -> Outcome<Decision> {
+            if expense.amount <= self.limit {
+                Outcome::Stop(Decision::Approved { by: self.role })
+            } else {
+                Outcome::Continue
             }
         }
+
+        fn name(&self) -> &'static str {
+            self.role
+        }
     }
 
-    mod reception {
-        use super::{into_next, Department, Patient};
+    /// Turns down anything nobody could approve.
+    pub struct Board;
 
-        #[derive(Default)]
-        pub struct Reception {
-            next: Option<Box<dyn Department>>,
+    impl Handler<Expense, Decision> for Board {
+        fn handle(&mut self, expense: &mut Expense) -> Outcome<Decision> {
+            Outcome::Reject(Decision::Denied {
+                by: "board",
+                reason: format!("{} is over every approval limit", expense.amount),
+            })
         }
 
-        impl Reception {
-            pub fn new(next: impl Department + 'static) -> Self {
-                Self {
-                    next: into_next(next),
-                }
+        fn name(&self) -> &'static str {
+            "board"
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::{Approver, Board, Decision, Expense};
+        use crate::handler::{Handler, Link, Outcome, Pipeline};
+
+        fn team_lead() -> Approver {
+            Approver {
+                role: "team lead",
+                limit: 1_000,
             }
         }
 
-        impl Department for Reception {
-            fn handle(&mut self, patient: &mut Patient) {
-                if patient.registration_done {
-                    println!("Patient registration is already done");
-                } else {
-                    println!("Reception registering a patient {}", patient.name);
-                    patient.registration_done = true;
-                }
+        fn manager() -> Approver {
+            Approver {
+                role: "manager",
+                limit: 10_000,
             }
+        }
 
-            fn next(&mut self) -> &mut Option<Box<dyn Department>> {
-                &mut self.next
+        fn expense(amount: u32) -> Expense {
+            Expense {
+                description: "Laptops".into(),
+                amount,
             }
         }
+
+        fn approved(by: &'static str) -> Outcome<Decision> {
+            Outcome::Stop(Decision::Approved { by })
+        }
+
+        #[test]
+        fn linked_approvers_stop_at_the_first_one_within_its_limit() {
+            let mut approvals = Link::new(team_lead()).then(manager()).then(Board);
+
+            assert_eq!(approvals.execute(&mut expense(150)), approved("team lead"));
+            assert_eq!(approvals.execute(&mut expense(8_000)), approved("manager"));
+            assert_eq!(
+                approvals.execute(&mut expense(250_000)),
+                Outcome::Reject(Decision::Denied {
+                    by: "board",
+                    reason: "250000 is over every approval limit".into(),
+                })
+            );
+            assert_eq!(approvals.name(), "team lead");
+        }
+
+        #[test]
+        fn request_nobody_decides_on_goes_through_the_whole_chain() {
+            let mut link = Link::new(team_lead()).then(team_lead());
+            let mut pipeline = Pipeline::new(vec![Box::new(team_lead()), Box::new(team_lead())]);
+
+            assert_eq!(link.execute(&mut expense(5_000)), Outcome::Continue);
+            assert_eq!(pipeline.execute(&mut expense(5_000)), Outcome::Continue);
+            assert_eq!(
+                Pipeline::<Expense, Decision>::default().execute(&mut expense(5_000)),
+                Outcome::Continue
+            );
+        }
+
+        #[test]
+        fn pipeline_stops_at_the_first_handler_that_decides() {
+            let mut pipeline = Pipeline::new(vec![Box::new(Board), Box::new(team_lead())]);
+
+            // The team lead would approve it, but is never asked.
+            assert!(matches!(
+                pipeline.execute(&mut expense(150)),
+                Outcome::Reject(Decision::Denied { by: "board", .. })
+            ));
+        }
+
+        #[test]
+        fn pipeline_is_a_handler_in_a_linked_chain() {
+            let leads = Pipeline::new(vec![Box::new(team_lead()), Box::new(manager())]);
+            let mut approvals = Link::new(leads).then(Board);
+
+            assert_eq!(approvals.name(), "pipeline");
+            assert_eq!(approvals.execute(&mut expense(8_000)), approved("manager"));
+            assert!(matches!(
+                approvals.execute(&mut expense(50_000)),
+                Outcome::Reject(Decision::Denied { by: "board", .. })
+            ));
+        }
     }
 }
 
 fn main() {
-    use department::{Cashier, Department, Doctor, Medical, Reception};
-    use patient::Patient;
+    use std::time::Duration;
+
+    use approval::{Approver, Board, Expense};
+    use async_department::{AsyncChain, Laboratory, PaymentGateway};
+    use chain::Chain;
+    use department::{Cashier, Doctor, Lab, Medical, Outcome, Radiology, Reception};
+    use handler::{Branch, FanOut, Link, Pipeline};
+    use hospital::Hospital;
+    use patient::{Finding, Insurance, Patient, Priority, Symptom};
 
-    let cashier = Cashier::default();
-    let medical = Medical::new(cashier);
-    let doctor = Doctor::new(medical);
-    let mut reception = Reception::new(doctor);
+    let mut reception = Link::new(Reception)
+        .then(Doctor)
+        .then(Medical)
+        .then(Cashier);
 
     let mut patient = Patient {
         name: "John".into(),
+        can_pay: true,
         ..Patient::default()
     };
 
@@ -198,4 +2317,239 @@ fn main() {
     println!("\nThe patient has been already handled:\n");
 
     reception.execute(&mut patient);
+    println!("\n{}", patient.visit_summary());
+
+    // Any department may stop the chain early, with a result or an error.
+    let patients = [
+        Patient {
+            name: "Jane".into(),
+            ..Patient::default()
+        },
+        Patient {
+            name: "Jack".into(),
+            priority: Priority::Emergency,
+            ..Patient::default()
+        },
+    ];
+
+    for mut patient in patients {
+        println!();
+        match reception.execute(&mut patient) {
+            Outcome::Continue | Outcome::Skip => {
+                println!("{} has been discharged", patient.name)
+            }
+            Outcome::Stop(result) => println!("Stopped: {}", result),
+            Outcome::Reject(error) => println!("Rejected: {}", error),
+        }
+        print!("{}", patient.visit_summary());
+    }
+
+    // A chain can also be assembled from a list, in the order departments
+    // are visited, and rearranged at runtime.
+    let mut chain = Chain::new(vec![Box::new(Reception), Box::new(Cashier)]);
+    chain.insert(1, Doctor);
+    chain.push(Medical);
+    chain.move_to("medical", 2);
+    println!("\nChain: {}", chain.names().join(" -> "));
+
+    chain.move_to("cashier", 1);
+    chain.remove("medical");
+    println!("Rearranged: {}\n", chain.names().join(" -> "));
+
+    let mut patient = Patient {
+        name: "Jill".into(),
+        can_pay: true,
+        ..Patient::default()
+    };
+    chain.execute(&mut patient);
+
+    // Hospitals with different workflows are described in config files.
+    let hospitals = concat!(env!("CARGO_MANIFEST_DIR"), "/hospitals");
+    for hospital in ["general", "walk_in_clinic"] {
+        match Chain::load(format!("{}/{}.chain", hospitals, hospital)) {
+            Ok(mut chain) => {
+                println!("\n{}: {}", hospital, chain.names().join(" -> "));
+
+                let mut patient = Patient {
+                    name: "Joe".into(),
+                    ..Patient::default()
+                };
+                println!("{:?}", chain.execute(&mut patient));
+            }
+            Err(err) => println!("Cannot load {}: {}", hospital, err),
+        }
+    }
+
+    if let Err(err) = Chain::from_config("reception\nsurgery\n") {
+        println!("\nInvalid config: {}", err);
+    }
+
+    // The front door queues patients by triage priority: the most urgent
+    // are seen first, the rest in order of arrival.
+    let mut hospital = Hospital::new(
+        Chain::load(format!("{}/general.chain", hospitals)).expect("a valid chain config"),
+    );
+
+    hospital.admit(Patient {
+        name: "Ann".into(),
+        priority: Priority::NonUrgent,
+        symptoms: vec![Symptom::Rash],
+        can_pay: true,
+        ..Patient::default()
+    });
+    hospital.admit(Patient {
+        name: "Bob".into(),
+        symptoms: vec![Symptom::Fever, Symptom::Cough, Symptom::Headache],
+        insurance: Some(Insurance {
+            provider: "Acme Health".into(),
+            consultation_co_pay: 1500,
+            coverage: 80,
+        }),
+        can_pay: true,
+        ..Patient::default()
+    });
+    hospital.admit(Patient {
+        name: "Cid".into(),
+        priority: Priority::Urgent,
+        symptoms: vec![Symptom::Pain, Symptom::Headache],
+        insurance: Some(Insurance {
+            provider: "Full Cover".into(),
+            consultation_co_pay: 0,
+            coverage: 100,
+        }),
+        ..Patient::default()
+    });
+    hospital.admit(Patient {
+        name: "Dee".into(),
+        priority: Priority::Emergency,
+        ..Patient::default()
+    });
+
+    println!("\n{} patients waiting", hospital.waiting());
+    for (patient, outcome) in hospital.treat_all() {
+        let insurer = patient
+            .insurance
+            .as_ref()
+            .map_or("uninsured", |insurance| insurance.provider.as_str());
+        println!(
+            "{} ({:?}, {}): {:?}\n",
+            patient.name, patient.priority, insurer, outcome
+        );
+    }
+
+    // The doctor's findings decide where a patient goes next: Radiology, the
+    // Lab, or both at once. Either way the patient rejoins the chain at
+    // Medical.
+    let diagnostics = Branch::new("diagnostics")
+        .when(
+            |patient: &Patient| patient.findings.len() > 1,
+            FanOut::new("tests", vec![Box::new(Radiology), Box::new(Lab)]),
+        )
+        .when(
+            |patient: &Patient| patient.findings.contains(&Finding::SuspectedFracture),
+            Radiology,
+        )
+        .otherwise(Lab);
+
+    let mut chain = Chain::new(vec![
+        Box::new(Reception),
+        Box::new(Doctor),
+        Box::new(diagnostics),
+        Box::new(Medical),
+        Box::new(Cashier),
+    ]);
+    println!("Chain: {}", chain.names().join(" -> "));
+
+    let patients = [
+        ("Eve", vec![Symptom::Pain]),
+        ("Fay", vec![Symptom::Fever]),
+        ("Gus", vec![Symptom::Pain, Symptom::Cough]),
+        ("Hal", vec![Symptom::Rash]),
+    ];
+
+    for (name, symptoms) in patients {
+        let mut patient = Patient {
+            name: name.into(),
+            symptoms,
+            insurance: Some(Insurance {
+                provider: "Acme Health".into(),
+                consultation_co_pay: 1500,
+                coverage: 50,
+            }),
+            can_pay: true,
+            ..Patient::default()
+        };
+
+        println!();
+        chain.execute(&mut patient);
+        print!("{}", patient.visit_summary());
+    }
+    println!();
+
+    // Departments that wait on others are run on an async executor, each
+    // with its own time limit.
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_time()
+        .build()
+        .expect("a local runtime");
+
+    let mut chain = AsyncChain::default();
+    chain.push(Reception);
+    chain.push(Doctor);
+    chain.push_with_timeout(
+        Laboratory::new(Duration::from_millis(50)),
+        Duration::from_millis(200),
+    );
+    chain.push_with_timeout(
+        PaymentGateway::new(Duration::from_millis(300)),
+        Duration::from_millis(100),
+    );
+
+    let mut patient = Patient {
+        name: "Jenny".into(),
+        can_pay: true,
+        ..Patient::default()
+    };
+
+    println!();
+    let outcome = runtime.block_on(chain.execute(&mut patient));
+    println!("{:?}\n", outcome);
+
+    // Every visit is traced, so it can be audited afterwards.
+    println!("{}", patient.visit_summary());
+    println!("{}", patient.trace.to_json());
+
+    // The same handler machinery serves other kinds of requests, e.g. an
+    // approval workflow, both as linked handlers and as a pipeline.
+    let mut approvals = Link::new(Approver {
+        role: "team lead",
+        limit: 1_000,
+    })
+    .then(Approver {
+        role: "manager",
+        limit: 10_000,
+    })
+    .then(Board);
+
+    let mut pipeline = Pipeline::new(vec![
+        Box::new(Approver {
+            role: "team lead",
+            limit: 1_000,
+        }),
+        Box::new(Board),
+    ]);
+
+    println!();
+    for (description, amount) in [("Keyboard", 150), ("Laptops", 8_000), ("Office", 250_000)] {
+        let mut expense = Expense {
+            description: description.into(),
+            amount,
+        };
+        let decision = approvals.execute(&mut expense);
+        let without_manager = pipeline.execute(&mut expense);
+        println!(
+            "{} ({}): {:?} / without a manager: {:?}",
+            expense.description, expense.amount, decision, without_manager
+        );
+    }
 }
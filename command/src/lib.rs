@@ -0,0 +1,2788 @@
+/*
+Converts requests or simple op into objects.
+
+Allows deferred or remote execution of commands, storing command history, etc.
+
+In Rust, a command interface should NOT hold a permanent reference to global, but
+be passed from top to down as mutable
+
+Demo: Text Editor
+- Each button runs a separate command
+- A command repr as object, can be pushed into history for undoing and redoing
+- Documents are saved and opened along with their undo history
+- Commands are found by name in a palette, or bound to keys in keys.conf
+- Other processes submit commands over a local socket, queued until the editor runs them
+- Commands work on an editor model, so they don't depend on any UI
+- TUI is created with cursive crate, as one of possible frontends, in tui/
+*/
+
+// Receiver
+pub mod editor {
+    use std::{
+        ffi::OsString,
+        fs, io,
+        ops::Range,
+        path::{Path, PathBuf},
+    };
+
+    use serde::{Deserialize, Serialize};
+
+    use crate::{
+        command::{Command, CommandError},
+        history::History,
+    };
+
+    /// A text document being edited, with a selection, its undo history,
+    /// and a clipboard shared by all commands.
+    ///
+    /// Positions are byte offsets into the text. An empty selection is just
+    /// a cursor.
+    #[derive(Default)]
+    pub struct Editor {
+        text: String,
+        selection: Range<usize>,
+        path: Option<PathBuf>,
+        saved: String,
+        pub history: History,
+        pub clipboard: String,
+        /// A message about the latest command, e.g. why a file was not saved.
+        pub status: Option<String>,
+    }
+
+    /// A range of text replaced with another one, e.g. inserted, deleted or
+    /// pasted. Applying or reverting it touches only that range.
+    #[derive(Clone, Serialize, Deserialize)]
+    pub struct Edit {
+        pub at: usize,
+        pub removed: String,
+        pub inserted: String,
+    }
+
+    impl Editor {
+        /// Executes a command, and keeps it in history if it has changed
+        /// the document. Returns whether it has.
+        pub fn execute(&mut self, mut command: Box<dyn Command>) -> Result<bool, CommandError> {
+            self.status = None;
+            let changed = command.execute(self)?;
+            if changed {
+                self.history.record(command);
+            }
+            Ok(changed)
+        }
+
+        /// Undoes the latest command. Returns `false` if there is none.
+        pub fn undo(&mut self) -> bool {
+            match self.history.pop_undo() {
+                Some(mut command) => {
+                    command.undo(self);
+                    self.history.push_redo(command);
+                    true
+                }
+                None => false,
+            }
+        }
+
+        /// Redoes the latest undone command. Returns `false` if there is none.
+        pub fn redo(&mut self) -> bool {
+            match self.history.pop_redo() {
+                Some(mut command) => {
+                    command.redo(self);
+                    self.history.push_undo(command);
+                    true
+                }
+                None => false,
+            }
+        }
+
+        pub fn text(&self) -> &str {
+            &self.text
+        }
+
+        pub fn path(&self) -> Option<&Path> {
+            self.path.as_deref()
+        }
+
+        /// Whether the text differs from the one last opened or saved.
+        pub fn is_dirty(&self) -> bool {
+            self.text != self.saved
+        }
+
+        /// Replaces the document with a file, along with the undo history
+        /// saved next to it.
+        pub fn open(&mut self, path: impl Into<PathBuf>) -> io::Result<()> {
+            let path = path.into();
+            let text = fs::read_to_string(&path)?;
+
+            // A broken history file must not keep the document from opening.
+            if let Err(err) = self.history.load(history_path(&path), &text) {
+                self.status = Some(format!("Undo history is not restored: {}", err));
+            }
+            self.saved = text.clone();
+            self.text = text;
+            self.path = Some(path);
+            self.set_cursor(0);
+            Ok(())
+        }
+
+        /// Writes the document to a file, and the undo history next to it.
+        pub fn save_as(&mut self, path: impl Into<PathBuf>) -> io::Result<()> {
+            let path = path.into();
+
+            fs::write(&path, &self.text)?;
+            self.history.save(history_path(&path), &self.text)?;
+            self.saved = self.text.clone();
+            self.path = Some(path);
+            Ok(())
+        }
+
+        pub fn selection(&self) -> Range<usize> {
+            self.selection.clone()
+        }
+
+        pub fn selected(&self) -> &str {
+            &self.text[self.selection()]
+        }
+
+        pub fn cursor(&self) -> usize {
+            self.selection.end
+        }
+
+        /// Selects a range, which is cut to the text and to whole characters.
+        pub fn select(&mut self, range: Range<usize>) {
+            let end = self.boundary(range.end);
+            let start = self.boundary(range.start.min(end));
+            self.selection = start..end;
+        }
+
+        pub fn set_cursor(&mut self, at: usize) {
+            self.select(at..at);
+        }
+
+        /// The start of the character before a position.
+        pub fn previous(&self, at: usize) -> usize {
+            let at = self.boundary(at);
+            self.text[..at]
+                .chars()
+                .next_back()
+                .map_or(at, |char| at - char.len_utf8())
+        }
+
+        /// Replaces a range with some text, and puts the cursor after it.
+        pub fn replace(&mut self, range: Range<usize>, text: &str) -> Edit {
+            let edit = Edit {
+                at: range.start,
+                removed: self.text[range].to_string(),
+                inserted: text.to_string(),
+            };
+            self.apply(&edit);
+            edit
+        }
+
+        pub fn apply(&mut self, edit: &Edit) {
+            let end = edit.at + edit.removed.len();
+            self.text.replace_range(edit.at..end, &edit.inserted);
+            self.set_cursor(edit.at + edit.inserted.len());
+        }
+
+        /// Puts the removed text back, and selects it.
+        pub fn revert(&mut self, edit: &Edit) {
+            let end = edit.at + edit.inserted.len();
+            self.text.replace_range(edit.at..end, &edit.removed);
+            self.select(edit.at..edit.at + edit.removed.len());
+        }
+
+        fn boundary(&self, at: usize) -> usize {
+            let mut at = at.min(self.text.len());
+            while !self.text.is_char_boundary(at) {
+                at -= 1;
+            }
+            at
+        }
+    }
+
+    /// Undo history of `notes.txt` is kept in `notes.txt.history`.
+    fn history_path(path: &Path) -> PathBuf {
+        let mut name = OsString::from(path.as_os_str());
+        name.push(".history");
+        PathBuf::from(name)
+    }
+}
+
+// Command Interface
+pub mod command {
+    // mod action;
+    // mod composite;
+    // mod copy;
+    // mod cut;
+    // mod delete;
+    // mod file;
+    // mod insert;
+    // mod paste;
+    // mod replace_all;
+
+    pub use action::Action;
+    pub use composite::CompositeCommand;
+    pub use copy::CopyCommand;
+    pub use cut::CutCommand;
+    pub use delete::DeleteCommand;
+    pub use file::{OpenFileCommand, SaveAsCommand, SaveCommand};
+    pub use insert::InsertCommand;
+    pub use paste::PasteCommand;
+    pub use replace_all::ReplaceAllCommand;
+
+    use std::{any::Any, fmt, io, path::PathBuf};
+
+    use crate::editor::{Edit, Editor};
+
+    #[derive(Debug)]
+    pub enum CommandError {
+        Open(PathBuf, io::Error),
+        Save(PathBuf, io::Error),
+        /// The document has never been saved, so there is no file to save
+        /// it to.
+        Untitled,
+        NothingSelected,
+        /// A command that can't be undone, by its label, is part of several
+        /// commands to be undone as one.
+        NotUndoable(String),
+    }
+
+    impl fmt::Display for CommandError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                CommandError::Open(path, err) => {
+                    write!(f, "cannot open {}: {}", path.display(), err)
+                }
+                CommandError::Save(path, err) => {
+                    write!(f, "cannot save {}: {}", path.display(), err)
+                }
+                CommandError::Untitled => write!(f, "the document has no file yet"),
+                CommandError::NothingSelected => write!(f, "nothing is selected"),
+                CommandError::NotUndoable(label) => {
+                    write!(
+                        f,
+                        "{} cannot be undone, so it cannot run with other commands",
+                        label
+                    )
+                }
+            }
+        }
+    }
+
+    impl std::error::Error for CommandError {}
+
+    /// Declares a method for executing (and undoing) a command.
+    ///
+    /// Each command receives an editor to access the document, its selection
+    /// and a clipboard, whatever frontend shows them.
+    pub trait Command: Any {
+        /// Returns `true` if the command has changed the document, and should
+        /// be kept in history to be undone.
+        fn execute(&mut self, editor: &mut Editor) -> Result<bool, CommandError>;
+        fn undo(&mut self, editor: &mut Editor);
+
+        /// Applies an undone command again.
+        fn redo(&mut self, editor: &mut Editor) {
+            // It has already succeeded on the very same document.
+            let _ = self.execute(editor);
+        }
+
+        /// Describes the command in a history menu.
+        fn label(&self) -> String;
+
+        /// Describes how to create the command again, e.g. to replay it.
+        fn action(&self) -> Action;
+
+        /// Changes made to the document, in order, e.g. to save them with it.
+        fn edits(&self) -> Vec<Edit> {
+            vec![]
+        }
+
+        /// Takes in the command that follows this one, so both are undone in
+        /// one step. Returns `false` if they are kept apart.
+        fn merge(&mut self, _next: &dyn Command) -> bool {
+            false
+        }
+    }
+
+    mod action {
+        use std::path::PathBuf;
+
+        use serde::{Deserialize, Serialize};
+
+        use super::{
+            Command, CompositeCommand, CopyCommand, CutCommand, DeleteCommand, InsertCommand,
+            OpenFileCommand, PasteCommand, ReplaceAllCommand, SaveAsCommand, SaveCommand,
+        };
+        use crate::macros::MacroCommand;
+
+        /// A command with its arguments, which can be stored, and turned
+        /// into a new command at any time.
+        #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+        #[serde(tag = "command", rename_all = "snake_case")]
+        pub enum Action {
+            Copy,
+            Cut,
+            Delete,
+            Insert { text: String },
+            Paste,
+            ReplaceAll { find: String, replacement: String },
+            Macro { name: String, actions: Vec<Action> },
+            Composite { label: String, actions: Vec<Action> },
+            Open { path: PathBuf },
+            Save,
+            SaveAs { path: PathBuf },
+        }
+
+        impl Action {
+            pub fn command(&self) -> Box<dyn Command> {
+                match self {
+                    Action::Copy => Box::new(CopyCommand),
+                    Action::Cut => Box::new(CutCommand::default()),
+                    Action::Delete => Box::new(DeleteCommand::default()),
+                    Action::Insert { text } => Box::new(InsertCommand::new(text.as_str())),
+                    Action::Paste => Box::new(PasteCommand::default()),
+                    Action::ReplaceAll { find, replacement } => {
+                        Box::new(ReplaceAllCommand::new(find.as_str(), replacement.as_str()))
+                    }
+                    Action::Macro { name, actions } => {
+                        Box::new(MacroCommand::new(name.as_str(), actions.clone()))
+                    }
+                    Action::Composite { label, actions } => Box::new(CompositeCommand::new(
+                        label.as_str(),
+                        actions.iter().map(Action::command).collect(),
+                    )),
+                    Action::Open { path } => Box::new(OpenFileCommand::new(path)),
+                    Action::Save => Box::new(SaveCommand),
+                    Action::SaveAs { path } => Box::new(SaveAsCommand::new(path)),
+                }
+            }
+
+            /// Tells whether undoing the command takes back everything it
+            /// has done. Files that have been opened or saved stay so.
+            pub fn is_undoable(&self) -> bool {
+                match self {
+                    Action::Open { .. } | Action::Save | Action::SaveAs { .. } => false,
+                    Action::Macro { actions, .. } | Action::Composite { actions, .. } => {
+                        actions.iter().all(Action::is_undoable)
+                    }
+                    _ => true,
+                }
+            }
+        }
+    }
+
+    mod composite {
+        use super::{Action, Command, CommandError};
+        use crate::editor::{Edit, Editor};
+
+        /// Several commands executed in order as one, which are undone and
+        /// redone together.
+        ///
+        /// If any of them fails, the ones executed before it are undone in
+        /// reverse order, so the document is left as it was. That's why
+        /// commands which can't be undone, like opening a file, are refused
+        /// before anything is executed.
+        pub struct CompositeCommand {
+            label: String,
+            commands: Vec<Box<dyn Command>>,
+            /// Indices of commands that have changed the document.
+            changed: Vec<usize>,
+        }
+
+        impl CompositeCommand {
+            pub fn new(label: impl Into<String>, commands: Vec<Box<dyn Command>>) -> Self {
+                Self {
+                    label: label.into(),
+                    commands,
+                    changed: vec![],
+                }
+            }
+        }
+
+        impl Command for CompositeCommand {
+            fn execute(&mut self, editor: &mut Editor) -> Result<bool, CommandError> {
+                self.changed.clear();
+
+                let mut commands = self.commands.iter();
+                if let Some(command) = commands.find(|command| !command.action().is_undoable()) {
+                    return Err(CommandError::NotUndoable(command.label()));
+                }
+
+                for index in 0..self.commands.len() {
+                    match self.commands[index].execute(editor) {
+                        Ok(true) => self.changed.push(index),
+                        Ok(false) => {}
+                        Err(err) => {
+                            self.undo(editor);
+                            self.changed.clear();
+                            return Err(err);
+                        }
+                    }
+                }
+
+                Ok(!self.changed.is_empty())
+            }
+
+            fn undo(&mut self, editor: &mut Editor) {
+                for &index in self.changed.iter().rev() {
+                    self.commands[index].undo(editor);
+                }
+            }
+
+            fn redo(&mut self, editor: &mut Editor) {
+                for &index in &self.changed {
+                    self.commands[index].redo(editor);
+                }
+            }
+
+            fn edits(&self) -> Vec<Edit> {
+                self.changed
+                    .iter()
+                    .flat_map(|&index| self.commands[index].edits())
+                    .collect()
+            }
+
+            fn label(&self) -> String {
+                self.label.clone()
+            }
+
+            fn action(&self) -> Action {
+                Action::Composite {
+                    label: self.label.clone(),
+                    actions: self
+                        .commands
+                        .iter()
+                        .map(|command| command.action())
+                        .collect(),
+                }
+            }
+        }
+    }
+
+    mod copy {
+        use super::{Action, Command, CommandError};
+        use crate::editor::Editor;
+
+        /// Copies the selection to the clipboard. It fails with nothing
+        /// selected, like cutting, and the clipboard is left as it is.
+        #[derive(Default)]
+        pub struct CopyCommand;
+
+        impl Command for CopyCommand {
+            fn execute(&mut self, editor: &mut Editor) -> Result<bool, CommandError> {
+                if editor.selected().is_empty() {
+                    return Err(CommandError::NothingSelected);
+                }
+
+                editor.clipboard = editor.selected().to_string();
+                Ok(false)
+            }
+
+            fn undo(&mut self, _: &mut Editor) {}
+
+            fn label(&self) -> String {
+                "Copy".into()
+            }
+
+            fn action(&self) -> Action {
+                Action::Copy
+            }
+        }
+    }
+
+    mod cut {
+        use super::{Action, Command, CommandError};
+        use crate::editor::{Edit, Editor};
+
+        /// Moves the selection to the clipboard. It fails with nothing
+        /// selected, like copying, and the clipboard is left as it is.
+        #[derive(Default)]
+        pub struct CutCommand {
+            edit: Option<Edit>,
+        }
+
+        impl Command for CutCommand {
+            fn execute(&mut self, editor: &mut Editor) -> Result<bool, CommandError> {
+                if editor.selected().is_empty() {
+                    return Err(CommandError::NothingSelected);
+                }
+
+                editor.clipboard = editor.selected().to_string();
+                self.edit = Some(editor.replace(editor.selection(), ""));
+                Ok(true)
+            }
+
+            fn undo(&mut self, editor: &mut Editor) {
+                if let Some(edit) = &self.edit {
+                    editor.revert(edit);
+                }
+            }
+
+            fn redo(&mut self, editor: &mut Editor) {
+                if let Some(edit) = &self.edit {
+                    editor.apply(edit);
+                }
+            }
+
+            fn edits(&self) -> Vec<Edit> {
+                self.edit.iter().cloned().collect()
+            }
+
+            fn label(&self) -> String {
+                "Cut".into()
+            }
+
+            fn action(&self) -> Action {
+                Action::Cut
+            }
+        }
+    }
+
+    mod delete {
+        use super::{Action, Command, CommandError};
+        use crate::editor::{Edit, Editor};
+
+        /// Deletes the selection, or the character before the cursor.
+        #[derive(Default)]
+        pub struct DeleteCommand {
+            edit: Option<Edit>,
+        }
+
+        impl Command for DeleteCommand {
+            fn execute(&mut self, editor: &mut Editor) -> Result<bool, CommandError> {
+                let mut range = editor.selection();
+                if range.is_empty() {
+                    range.start = editor.previous(range.end);
+                }
+                if range.is_empty() {
+                    return Ok(false);
+                }
+
+                self.edit = Some(editor.replace(range, ""));
+                Ok(true)
+            }
+
+            fn undo(&mut self, editor: &mut Editor) {
+                if let Some(edit) = &self.edit {
+                    editor.revert(edit);
+                }
+            }
+
+            fn redo(&mut self, editor: &mut Editor) {
+                if let Some(edit) = &self.edit {
+                    editor.apply(edit);
+                }
+            }
+
+            fn edits(&self) -> Vec<Edit> {
+                self.edit.iter().cloned().collect()
+            }
+
+            fn label(&self) -> String {
+                match &self.edit {
+                    Some(edit) => format!("Delete \"{}\"", edit.removed),
+                    None => "Delete".into(),
+                }
+            }
+
+            fn action(&self) -> Action {
+                Action::Delete
+            }
+        }
+    }
+
+    mod file {
+        use std::path::PathBuf;
+
+        use super::{Action, Command, CommandError};
+        use crate::editor::Editor;
+
+        /// Opens a file in place of the document, with its undo history.
+        /// Unsaved changes are lost.
+        pub struct OpenFileCommand {
+            path: PathBuf,
+        }
+
+        impl OpenFileCommand {
+            pub fn new(path: impl Into<PathBuf>) -> Self {
+                Self { path: path.into() }
+            }
+        }
+
+        impl Command for OpenFileCommand {
+            fn execute(&mut self, editor: &mut Editor) -> Result<bool, CommandError> {
+                match editor.open(&self.path) {
+                    Ok(()) => Ok(false),
+                    Err(err) => Err(CommandError::Open(self.path.clone(), err)),
+                }
+            }
+
+            fn undo(&mut self, _: &mut Editor) {}
+
+            fn label(&self) -> String {
+                format!("Open {}", self.path.display())
+            }
+
+            fn action(&self) -> Action {
+                Action::Open {
+                    path: self.path.clone(),
+                }
+            }
+        }
+
+        /// Saves the document to the file it was opened from or saved to.
+        #[derive(Default)]
+        pub struct SaveCommand;
+
+        impl Command for SaveCommand {
+            fn execute(&mut self, editor: &mut Editor) -> Result<bool, CommandError> {
+                match editor.path().map(|path| path.to_path_buf()) {
+                    Some(path) => SaveAsCommand::new(path).execute(editor),
+                    None => Err(CommandError::Untitled),
+                }
+            }
+
+            fn undo(&mut self, _: &mut Editor) {}
+
+            fn label(&self) -> String {
+                "Save".into()
+            }
+
+            fn action(&self) -> Action {
+                Action::Save
+            }
+        }
+
+        /// Saves the document to a new file, which is used from now on.
+        pub struct SaveAsCommand {
+            path: PathBuf,
+        }
+
+        impl SaveAsCommand {
+            pub fn new(path: impl Into<PathBuf>) -> Self {
+                Self { path: path.into() }
+            }
+        }
+
+        impl Command for SaveAsCommand {
+            fn execute(&mut self, editor: &mut Editor) -> Result<bool, CommandError> {
+                match editor.save_as(&self.path) {
+                    Ok(()) => Ok(false),
+                    Err(err) => Err(CommandError::Save(self.path.clone(), err)),
+                }
+            }
+
+            fn undo(&mut self, _: &mut Editor) {}
+
+            fn label(&self) -> String {
+                format!("Save as {}", self.path.display())
+            }
+
+            fn action(&self) -> Action {
+                Action::SaveAs {
+                    path: self.path.clone(),
+                }
+            }
+        }
+    }
+
+    mod insert {
+        use std::any::Any;
+
+        use super::{Action, Command, CommandError};
+        use crate::editor::{Edit, Editor};
+
+        /// Typed text, which replaces the selection.
+        pub struct InsertCommand {
+            text: String,
+            edit: Option<Edit>,
+        }
+
+        impl InsertCommand {
+            pub fn new(text: impl Into<String>) -> Self {
+                Self {
+                    text: text.into(),
+                    edit: None,
+                }
+            }
+        }
+
+        impl Command for InsertCommand {
+            fn execute(&mut self, editor: &mut Editor) -> Result<bool, CommandError> {
+                if self.text.is_empty() {
+                    return Ok(false);
+                }
+
+                self.edit = Some(editor.replace(editor.selection(), &self.text));
+                Ok(true)
+            }
+
+            fn undo(&mut self, editor: &mut Editor) {
+                if let Some(edit) = &self.edit {
+                    editor.revert(edit);
+                }
+            }
+
+            fn redo(&mut self, editor: &mut Editor) {
+                if let Some(edit) = &self.edit {
+                    editor.apply(edit);
+                }
+            }
+
+            fn edits(&self) -> Vec<Edit> {
+                self.edit.iter().cloned().collect()
+            }
+
+            fn label(&self) -> String {
+                format!("Typing \"{}\"", self.text)
+            }
+
+            fn action(&self) -> Action {
+                Action::Insert {
+                    text: self.text.clone(),
+                }
+            }
+
+            /// Text typed right after this one, with nothing selected, is
+            /// undone at once.
+            fn merge(&mut self, next: &dyn Command) -> bool {
+                let Some(next) = (next as &dyn Any).downcast_ref::<InsertCommand>() else {
+                    return false;
+                };
+
+                match (&mut self.edit, &next.edit) {
+                    (Some(edit), Some(next))
+                        if next.removed.is_empty() && next.at == edit.at + edit.inserted.len() =>
+                    {
+                        edit.inserted.push_str(&next.inserted);
+                        self.text.push_str(&next.inserted);
+                        true
+                    }
+                    _ => false,
+                }
+            }
+        }
+    }
+
+    mod paste {
+        use super::{Action, Command, CommandError};
+        use crate::editor::{Edit, Editor};
+
+        /// Pastes the clipboard at the cursor, in place of the selection.
+        #[derive(Default)]
+        pub struct PasteCommand {
+            edit: Option<Edit>,
+        }
+
+        impl Command for PasteCommand {
+            fn execute(&mut self, editor: &mut Editor) -> Result<bool, CommandError> {
+                if editor.clipboard.is_empty() {
+                    return Ok(false);
+                }
+
+                let clipboard = editor.clipboard.clone();
+                self.edit = Some(editor.replace(editor.selection(), &clipboard));
+                Ok(true)
+            }
+
+            fn undo(&mut self, editor: &mut Editor) {
+                if let Some(edit) = &self.edit {
+                    editor.revert(edit);
+                }
+            }
+
+            /// Pastes what was pasted the first time, even if the clipboard
+            /// has changed since.
+            fn redo(&mut self, editor: &mut Editor) {
+                if let Some(edit) = &self.edit {
+                    editor.apply(edit);
+                }
+            }
+
+            fn edits(&self) -> Vec<Edit> {
+                self.edit.iter().cloned().collect()
+            }
+
+            fn label(&self) -> String {
+                "Paste".into()
+            }
+
+            fn action(&self) -> Action {
+                Action::Paste
+            }
+        }
+    }
+
+    mod replace_all {
+        use super::{Action, Command, CommandError};
+        use crate::editor::{Edit, Editor};
+
+        pub struct ReplaceAllCommand {
+            find: String,
+            replacement: String,
+            edits: Vec<Edit>,
+        }
+
+        impl ReplaceAllCommand {
+            pub fn new(find: impl Into<String>, replacement: impl Into<String>) -> Self {
+                Self {
+                    find: find.into(),
+                    replacement: replacement.into(),
+                    edits: vec![],
+                }
+            }
+        }
+
+        impl Command for ReplaceAllCommand {
+            fn execute(&mut self, editor: &mut Editor) -> Result<bool, CommandError> {
+                if self.find.is_empty() {
+                    return Ok(false);
+                }
+
+                let found: Vec<usize> = editor
+                    .text()
+                    .match_indices(&self.find)
+                    .map(|(at, _)| at)
+                    .collect();
+
+                // Every replacement shifts the ones after it.
+                self.edits.clear();
+                for (replaced, at) in found.into_iter().enumerate() {
+                    let at = at + replaced * self.replacement.len() - replaced * self.find.len();
+                    let edit = editor.replace(at..at + self.find.len(), &self.replacement);
+                    self.edits.push(edit);
+                }
+
+                Ok(!self.edits.is_empty())
+            }
+
+            fn undo(&mut self, editor: &mut Editor) {
+                for edit in self.edits.iter().rev() {
+                    editor.revert(edit);
+                }
+            }
+
+            fn redo(&mut self, editor: &mut Editor) {
+                for edit in &self.edits {
+                    editor.apply(edit);
+                }
+            }
+
+            fn edits(&self) -> Vec<Edit> {
+                self.edits.clone()
+            }
+
+            fn label(&self) -> String {
+                format!("Replace \"{}\" with \"{}\"", self.find, self.replacement)
+            }
+
+            fn action(&self) -> Action {
+                Action::ReplaceAll {
+                    find: self.find.clone(),
+                    replacement: self.replacement.clone(),
+                }
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use std::io::Write;
+
+        use super::{
+            Action, Command, CommandError, CompositeCommand, CopyCommand, CutCommand,
+            DeleteCommand, InsertCommand, PasteCommand, ReplaceAllCommand,
+        };
+        use crate::{editor::Editor, macros::MacroCommand};
+
+        /// Fails after whatever ran before it, and has nothing to undo.
+        struct FailingCommand;
+
+        impl Command for FailingCommand {
+            fn execute(&mut self, _: &mut Editor) -> Result<bool, CommandError> {
+                Err(CommandError::Untitled)
+            }
+
+            fn undo(&mut self, _: &mut Editor) {}
+
+            fn label(&self) -> String {
+                "Fail".into()
+            }
+
+            fn action(&self) -> Action {
+                Action::Copy
+            }
+        }
+
+        fn editor(text: &str) -> Editor {
+            let mut editor = Editor::default();
+            editor.execute(Box::new(InsertCommand::new(text))).unwrap();
+            editor
+        }
+
+        #[test]
+        fn copy_fills_the_clipboard_and_is_not_undone() {
+            let mut editor = editor("hello world");
+            editor.select(0..5);
+
+            editor.execute(Box::new(CopyCommand)).unwrap();
+
+            assert_eq!(editor.clipboard, "hello");
+            assert_eq!(editor.text(), "hello world");
+            assert_eq!(editor.history.undo_labels().len(), 1);
+        }
+
+        #[test]
+        fn copy_without_selection_fails() {
+            let mut editor = editor("hello");
+            editor.clipboard = "kept".into();
+
+            let result = editor.execute(Box::new(CopyCommand));
+
+            assert!(matches!(result, Err(CommandError::NothingSelected)));
+            assert_eq!(editor.clipboard, "kept");
+        }
+
+        #[test]
+        fn cut_moves_the_selection_to_the_clipboard() {
+            let mut editor = editor("hello world");
+            editor.select(5..11);
+
+            editor.execute(Box::new(CutCommand::default())).unwrap();
+            assert_eq!(editor.clipboard, " world");
+            assert_eq!(editor.text(), "hello");
+
+            assert!(editor.undo());
+            assert_eq!(editor.text(), "hello world");
+            assert_eq!(editor.selected(), " world");
+
+            assert!(editor.redo());
+            assert_eq!(editor.text(), "hello");
+        }
+
+        #[test]
+        fn cut_without_selection_fails() {
+            let mut editor = editor("hello");
+            editor.clipboard = "kept".into();
+
+            let result = editor.execute(Box::new(CutCommand::default()));
+
+            assert!(matches!(result, Err(CommandError::NothingSelected)));
+            assert_eq!(editor.text(), "hello");
+            assert_eq!(editor.clipboard, "kept");
+            assert_eq!(editor.history.undo_labels().len(), 1);
+        }
+
+        #[test]
+        fn delete_removes_the_selection() {
+            let mut editor = editor("hello world");
+            editor.select(5..11);
+
+            editor.execute(Box::new(DeleteCommand::default())).unwrap();
+            assert_eq!(editor.text(), "hello");
+            assert_eq!(editor.history.undo_labels()[0], "Delete \" world\"");
+
+            assert!(editor.undo());
+            assert_eq!(editor.text(), "hello world");
+            assert_eq!(editor.selected(), " world");
+        }
+
+        #[test]
+        fn delete_removes_the_whole_character_before_the_cursor() {
+            let mut editor = editor("grün");
+            editor.set_cursor(4);
+
+            editor.execute(Box::new(DeleteCommand::default())).unwrap();
+            assert_eq!(editor.text(), "grn");
+            assert_eq!(editor.cursor(), 2);
+
+            // Only the character is put back, and selected.
+            assert!(editor.undo());
+            assert_eq!(editor.text(), "grün");
+            assert_eq!(editor.selection(), 2..4);
+            assert!(editor.redo());
+            assert_eq!(editor.text(), "grn");
+        }
+
+        #[test]
+        fn delete_at_the_start_does_nothing() {
+            let mut editor = editor("hello");
+            editor.set_cursor(0);
+
+            assert!(!editor.execute(Box::new(DeleteCommand::default())).unwrap());
+            assert_eq!(editor.text(), "hello");
+            assert_eq!(editor.history.undo_labels().len(), 1);
+        }
+
+        #[test]
+        fn replace_all_shifts_later_matches() {
+            for (replacement, offsets, replaced) in
+                [("xyz", [0, 4, 8], "xyz-xyz-xyz"), ("", [0, 1, 2], "--")]
+            {
+                let mut editor = editor("a-a-a");
+                let mut command = ReplaceAllCommand::new("a", replacement);
+
+                assert!(command.execute(&mut editor).unwrap());
+                assert_eq!(editor.text(), replaced);
+                let edits: Vec<usize> = command.edits().iter().map(|edit| edit.at).collect();
+                assert_eq!(edits, offsets);
+
+                command.undo(&mut editor);
+                assert_eq!(editor.text(), "a-a-a");
+            }
+        }
+
+        #[test]
+        fn replace_all_is_undone_on_multibyte_text() {
+            let mut editor = editor("Müller über Bücher");
+
+            editor
+                .execute(Box::new(ReplaceAllCommand::new("ü", "ue")))
+                .unwrap();
+            assert_eq!(editor.text(), "Mueller ueber Buecher");
+
+            assert!(editor.undo());
+            assert_eq!(editor.text(), "Müller über Bücher");
+            // The first match is the last one put back.
+            assert_eq!(editor.selection(), 1..3);
+            assert!(editor.redo());
+            assert_eq!(editor.text(), "Mueller ueber Buecher");
+        }
+
+        #[test]
+        fn replace_all_without_matches_does_nothing() {
+            let mut editor = editor("hello");
+
+            for find in ["", "x"] {
+                let replaced = editor.execute(Box::new(ReplaceAllCommand::new(find, "y")));
+                assert!(!replaced.unwrap());
+            }
+            assert_eq!(editor.text(), "hello");
+            assert_eq!(editor.history.undo_labels().len(), 1);
+        }
+
+        #[test]
+        fn paste_replaces_the_selection() {
+            let mut editor = editor("hello world");
+            editor.clipboard = "bye".into();
+            editor.select(0..5);
+
+            editor.execute(Box::new(PasteCommand::default())).unwrap();
+            assert_eq!(editor.text(), "bye world");
+            assert_eq!(editor.cursor(), 3);
+
+            assert!(editor.undo());
+            assert_eq!(editor.text(), "hello world");
+        }
+
+        #[test]
+        fn paste_redoes_what_was_pasted_first() {
+            let mut editor = editor("world");
+            editor.clipboard = "hello ".into();
+            editor.set_cursor(0);
+
+            editor.execute(Box::new(PasteCommand::default())).unwrap();
+            editor.undo();
+            editor.clipboard = "bye ".into();
+            editor.redo();
+
+            assert_eq!(editor.text(), "hello world");
+        }
+
+        #[test]
+        fn paste_with_empty_clipboard_does_nothing() {
+            let mut editor = editor("hello");
+
+            editor.execute(Box::new(PasteCommand::default())).unwrap();
+
+            assert_eq!(editor.text(), "hello");
+            assert_eq!(editor.history.undo_labels().len(), 1);
+        }
+
+        #[test]
+        fn composite_is_undone_and_redone_in_one_step() {
+            let mut editor = Editor::default();
+            let commands: Vec<Box<dyn Command>> = vec![
+                Box::new(InsertCommand::new("hello")),
+                Box::new(InsertCommand::new(" world")),
+            ];
+
+            editor
+                .execute(Box::new(CompositeCommand::new("Greet", commands)))
+                .unwrap();
+            assert_eq!(editor.text(), "hello world");
+            assert_eq!(editor.history.undo_labels(), ["Greet"]);
+
+            assert!(editor.undo());
+            assert_eq!(editor.text(), "");
+            assert!(editor.redo());
+            assert_eq!(editor.text(), "hello world");
+        }
+
+        #[test]
+        fn composite_is_rolled_back_when_a_command_fails() {
+            let mut editor = editor("abc");
+            let commands: Vec<Box<dyn Command>> = vec![
+                Box::new(InsertCommand::new("x")),
+                Box::new(DeleteCommand::default()),
+                Box::new(DeleteCommand::default()),
+                Box::new(FailingCommand),
+            ];
+
+            let result = editor.execute(Box::new(CompositeCommand::new("Edit", commands)));
+
+            assert!(matches!(result, Err(CommandError::Untitled)));
+            assert_eq!(editor.text(), "abc");
+            assert_eq!(editor.history.undo_labels().len(), 1);
+        }
+
+        #[test]
+        fn file_commands_are_refused_in_macros() {
+            let mut file = tempfile::NamedTempFile::new().unwrap();
+            file.write_all(b"x").unwrap();
+            let open = Action::Open {
+                path: file.path().into(),
+            };
+            let insert = Action::Insert {
+                text: "hello world".into(),
+            };
+
+            let mut editor = Editor::default();
+            for actions in [vec![insert.clone(), open.clone()], vec![open, insert]] {
+                let result = editor.execute(Box::new(MacroCommand::new("m", actions)));
+
+                assert!(matches!(result, Err(CommandError::NotUndoable(_))));
+                assert_eq!(editor.text(), "");
+                assert!(editor.path().is_none());
+                assert!(!editor.undo());
+            }
+        }
+    }
+}
+
+// History
+pub mod history {
+    use std::{collections::VecDeque, fs, io, path::Path};
+
+    use serde::{Deserialize, Serialize};
+
+    use crate::{
+        command::{Action, Command, CommandError},
+        editor::{Edit, Editor},
+    };
+
+    /// Commands that can be undone, and the undone ones that can be redone.
+    ///
+    /// Only the latest `limit` commands are kept; a new command clears
+    /// the redo stack.
+    pub struct History {
+        undo: VecDeque<Box<dyn Command>>,
+        redo: Vec<Box<dyn Command>>,
+        limit: usize,
+    }
+
+    impl Default for History {
+        fn default() -> Self {
+            Self::new(100)
+        }
+    }
+
+    impl History {
+        pub fn new(limit: usize) -> Self {
+            Self {
+                undo: VecDeque::new(),
+                redo: vec![],
+                limit,
+            }
+        }
+
+        /// Keeps a newly executed command to be undone, unless the latest
+        /// one takes it in.
+        pub fn record(&mut self, command: Box<dyn Command>) {
+            self.redo.clear();
+            if let Some(last) = self.undo.back_mut() {
+                if last.merge(command.as_ref()) {
+                    return;
+                }
+            }
+
+            self.push_undo(command);
+        }
+
+        pub fn pop_undo(&mut self) -> Option<Box<dyn Command>> {
+            self.undo.pop_back()
+        }
+
+        /// Keeps a command to be undone, leaving the redo stack as is.
+        pub fn push_undo(&mut self, command: Box<dyn Command>) {
+            self.undo.push_back(command);
+            while self.undo.len() > self.limit {
+                self.undo.pop_front();
+            }
+        }
+
+        pub fn pop_redo(&mut self) -> Option<Box<dyn Command>> {
+            self.redo.pop()
+        }
+
+        pub fn push_redo(&mut self, command: Box<dyn Command>) {
+            self.redo.push(command);
+        }
+
+        /// Labels of commands to be undone, the latest first.
+        pub fn undo_labels(&self) -> Vec<String> {
+            self.undo
+                .iter()
+                .rev()
+                .map(|command| command.label())
+                .collect()
+        }
+
+        /// Labels of commands to be redone, the next one first.
+        pub fn redo_labels(&self) -> Vec<String> {
+            self.redo
+                .iter()
+                .rev()
+                .map(|command| command.label())
+                .collect()
+        }
+
+        /// Writes the commands to be undone and redone with the edits they
+        /// made, which only fit the given document.
+        pub fn save(&self, path: impl AsRef<Path>, document: &str) -> io::Result<()> {
+            let saved = SavedHistory {
+                document: checksum(document),
+                undo: self
+                    .undo
+                    .iter()
+                    .map(|command| Entry::from(command.as_ref()))
+                    .collect(),
+                redo: self
+                    .redo
+                    .iter()
+                    .map(|command| Entry::from(command.as_ref()))
+                    .collect(),
+            };
+
+            fs::write(path, serde_json::to_string_pretty(&saved)?)
+        }
+
+        /// Replaces commands with the ones saved along with a document.
+        /// There are none if nothing was saved, or the document has been
+        /// changed since. Edits that don't fit the document are an error,
+        /// and leave no commands either.
+        pub fn load(&mut self, path: impl AsRef<Path>, document: &str) -> io::Result<()> {
+            self.undo.clear();
+            self.redo.clear();
+
+            let saved: SavedHistory = match fs::read_to_string(path) {
+                Ok(json) => serde_json::from_str(&json)?,
+                Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+                Err(err) => return Err(err),
+            };
+
+            if saved.document == checksum(document) {
+                if !saved.fits(document) {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "edits do not fit the document",
+                    ));
+                }
+
+                for entry in saved.undo {
+                    self.push_undo(Box::new(entry));
+                }
+                self.redo = saved
+                    .redo
+                    .into_iter()
+                    .map(|entry| Box::new(entry) as _)
+                    .collect();
+            }
+
+            Ok(())
+        }
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct SavedHistory {
+        document: u64,
+        undo: Vec<Entry>,
+        redo: Vec<Entry>,
+    }
+
+    impl SavedHistory {
+        /// Whether undoing every command, the latest first, finds the text
+        /// each edit inserted, and redoing them from the document finds the
+        /// text each edit removed, so replaying them can't go out of the
+        /// text or split a character.
+        fn fits(&self, document: &str) -> bool {
+            let mut text = document.to_string();
+            let undone = self
+                .undo
+                .iter()
+                .rev()
+                .flat_map(|entry| entry.edits.iter().rev());
+            for edit in undone {
+                if !replace(&mut text, edit.at, &edit.inserted, &edit.removed) {
+                    return false;
+                }
+            }
+
+            let mut text = document.to_string();
+            let redone = self.redo.iter().rev().flat_map(|entry| entry.edits.iter());
+            for edit in redone {
+                if !replace(&mut text, edit.at, &edit.removed, &edit.inserted) {
+                    return false;
+                }
+            }
+
+            true
+        }
+    }
+
+    /// Replaces the text expected at a position, if it's there.
+    fn replace(text: &mut String, at: usize, expected: &str, with: &str) -> bool {
+        match at.checked_add(expected.len()) {
+            Some(end) if text.get(at..end) == Some(expected) => {
+                text.replace_range(at..end, with);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Tells which document a history was saved with. It's FNV-1a rather
+    /// than the std hasher, whose algorithm may change between releases
+    /// and leave saved histories unusable.
+    fn checksum(document: &str) -> u64 {
+        document.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+        })
+    }
+
+    /// A command restored from a saved history, which only replays or
+    /// reverts the edits it made before.
+    #[derive(Serialize, Deserialize)]
+    struct Entry {
+        label: String,
+        action: Action,
+        edits: Vec<Edit>,
+    }
+
+    impl From<&dyn Command> for Entry {
+        fn from(command: &dyn Command) -> Self {
+            Self {
+                label: command.label(),
+                action: command.action(),
+                edits: command.edits(),
+            }
+        }
+    }
+
+    impl Command for Entry {
+        fn execute(&mut self, editor: &mut Editor) -> Result<bool, CommandError> {
+            self.redo(editor);
+            Ok(!self.edits.is_empty())
+        }
+
+        fn undo(&mut self, editor: &mut Editor) {
+            for edit in self.edits.iter().rev() {
+                editor.revert(edit);
+            }
+        }
+
+        fn redo(&mut self, editor: &mut Editor) {
+            for edit in &self.edits {
+                editor.apply(edit);
+            }
+        }
+
+        fn label(&self) -> String {
+            self.label.clone()
+        }
+
+        fn action(&self) -> Action {
+            self.action.clone()
+        }
+
+        fn edits(&self) -> Vec<Edit> {
+            self.edits.clone()
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use std::fs;
+
+        use serde_json::{json, Value};
+
+        use super::{checksum, History};
+        use crate::{command::InsertCommand, editor::Editor};
+
+        #[test]
+        fn checksum_is_fnv_1a() {
+            assert_eq!(checksum(""), 0xcbf2_9ce4_8422_2325);
+            assert_eq!(checksum("a"), 0xaf63_dc4c_8601_ec8c);
+            assert_eq!(checksum("foobar"), 0x8594_4171_f739_67e8);
+        }
+
+        /// Types each text at the start, so it isn't merged with the one
+        /// before.
+        fn typed(editor: &mut Editor, texts: &[&str]) {
+            for text in texts {
+                editor.set_cursor(0);
+                editor.execute(Box::new(InsertCommand::new(*text))).unwrap();
+            }
+        }
+
+        #[test]
+        fn oldest_commands_are_dropped_past_the_limit() {
+            let mut editor = Editor::default();
+            editor.history = History::new(2);
+            typed(&mut editor, &["a", "b", "c"]);
+
+            assert_eq!(
+                editor.history.undo_labels(),
+                ["Typing \"c\"", "Typing \"b\""]
+            );
+            assert!(editor.undo());
+            assert!(editor.undo());
+            assert!(!editor.undo());
+            assert_eq!(editor.text(), "a");
+        }
+
+        #[test]
+        fn redone_commands_are_kept_within_the_limit() {
+            let mut editor = Editor::default();
+            editor.history = History::new(2);
+            typed(&mut editor, &["a", "b"]);
+            editor.undo();
+            editor.undo();
+
+            assert!(editor.redo());
+            assert!(editor.redo());
+            assert_eq!(editor.text(), "ba");
+            assert_eq!(editor.history.undo_labels().len(), 2);
+        }
+
+        #[test]
+        fn new_command_clears_the_redo_stack() {
+            let mut editor = Editor::default();
+            typed(&mut editor, &["a", "b"]);
+            editor.undo();
+            assert_eq!(editor.history.redo_labels(), ["Typing \"b\""]);
+
+            typed(&mut editor, &["c"]);
+
+            assert!(editor.history.redo_labels().is_empty());
+            assert!(!editor.redo());
+            assert_eq!(editor.text(), "ca");
+        }
+
+        #[test]
+        fn consecutive_typing_is_undone_at_once() {
+            let mut editor = Editor::default();
+            for text in ["h", "e", "llo"] {
+                editor.execute(Box::new(InsertCommand::new(text))).unwrap();
+            }
+
+            assert_eq!(editor.history.undo_labels(), ["Typing \"hello\""]);
+            assert!(editor.undo());
+            assert_eq!(editor.text(), "");
+            assert!(editor.redo());
+            assert_eq!(editor.text(), "hello");
+        }
+
+        #[test]
+        fn typing_elsewhere_or_over_a_selection_is_not_merged() {
+            let mut editor = Editor::default();
+            editor
+                .execute(Box::new(InsertCommand::new("hello")))
+                .unwrap();
+            editor.set_cursor(0);
+            editor.execute(Box::new(InsertCommand::new(">"))).unwrap();
+            editor.select(1..6);
+            editor.execute(Box::new(InsertCommand::new("bye"))).unwrap();
+
+            assert_eq!(editor.text(), ">bye");
+            assert_eq!(editor.history.undo_labels().len(), 3);
+            assert!(editor.undo());
+            assert_eq!(editor.text(), ">hello");
+        }
+
+        #[test]
+        fn history_is_restored_when_the_document_is_opened() {
+            let dir = tempfile::tempdir().unwrap();
+            let path = dir.path().join("note.txt");
+            let mut editor = Editor::default();
+            editor
+                .execute(Box::new(InsertCommand::new("hello")))
+                .unwrap();
+            editor.set_cursor(0);
+            editor.execute(Box::new(InsertCommand::new("> "))).unwrap();
+            editor.undo();
+            editor.save_as(&path).unwrap();
+
+            let mut reopened = Editor::default();
+            reopened.open(&path).unwrap();
+            assert_eq!(reopened.text(), "hello");
+            assert_eq!(reopened.history.undo_labels(), editor.history.undo_labels());
+            assert_eq!(reopened.history.redo_labels(), editor.history.redo_labels());
+            assert!(reopened.status.is_none());
+
+            assert!(reopened.redo());
+            assert_eq!(reopened.text(), "> hello");
+            assert!(reopened.undo());
+            assert!(reopened.undo());
+            assert_eq!(reopened.text(), "");
+        }
+
+        #[test]
+        fn history_is_dropped_when_the_document_has_changed() {
+            let dir = tempfile::tempdir().unwrap();
+            let path = dir.path().join("note.txt");
+            let mut editor = Editor::default();
+            editor
+                .execute(Box::new(InsertCommand::new("hello")))
+                .unwrap();
+            editor.save_as(&path).unwrap();
+            fs::write(&path, "changed elsewhere").unwrap();
+
+            let mut reopened = Editor::default();
+            reopened.open(&path).unwrap();
+
+            assert_eq!(reopened.text(), "changed elsewhere");
+            assert!(reopened.history.undo_labels().is_empty());
+            assert!(!reopened.undo());
+        }
+
+        #[test]
+        fn history_is_dropped_when_its_edits_do_not_fit_the_document() {
+            let dir = tempfile::tempdir().unwrap();
+            let path = dir.path().join("note.txt");
+            let history = dir.path().join("note.txt.history");
+            let mut editor = Editor::default();
+            editor
+                .execute(Box::new(InsertCommand::new("héllo")))
+                .unwrap();
+            editor.set_cursor(0);
+            editor.execute(Box::new(InsertCommand::new("> "))).unwrap();
+            editor.undo();
+            editor.save_as(&path).unwrap();
+            let saved: Value =
+                serde_json::from_str(&fs::read_to_string(&history).unwrap()).unwrap();
+
+            let tampered: [fn(&mut Value); 4] = [
+                // Out of the text.
+                |saved| saved["undo"][0]["edits"][0]["at"] = json!(100),
+                // Not the text that was inserted.
+                |saved| saved["undo"][0]["edits"][0]["inserted"] = json!("hello"),
+                // In the middle of `é`.
+                |saved| {
+                    saved["undo"][0]["edits"][0]["at"] = json!(2);
+                    saved["undo"][0]["edits"][0]["inserted"] = json!("é");
+                },
+                // Not the text to be removed.
+                |saved| saved["redo"][0]["edits"][0]["removed"] = json!("x"),
+            ];
+            for tamper in tampered {
+                let mut saved = saved.clone();
+                tamper(&mut saved);
+                fs::write(&history, saved.to_string()).unwrap();
+
+                let mut reopened = Editor::default();
+                reopened.open(&path).unwrap();
+
+                assert_eq!(reopened.text(), "héllo");
+                let status = reopened.status.clone().unwrap();
+                assert!(status.starts_with("Undo history is not restored"));
+                assert!(reopened.history.undo_labels().is_empty());
+                assert!(reopened.history.redo_labels().is_empty());
+                assert!(!reopened.undo());
+                assert!(!reopened.redo());
+            }
+        }
+    }
+}
+
+// Macros
+pub mod macros {
+    use std::{collections::BTreeMap, fmt, fs, io, path::Path};
+
+    use crate::{
+        command::{Action, Command, CommandError, CompositeCommand},
+        editor::{Edit, Editor},
+    };
+
+    /// Plays recorded actions as a single command, so a whole playback is
+    /// undone in one step, or rolled back if any action fails.
+    pub struct MacroCommand {
+        name: String,
+        actions: Vec<Action>,
+        playback: CompositeCommand,
+    }
+
+    impl MacroCommand {
+        pub fn new(name: impl Into<String>, actions: Vec<Action>) -> Self {
+            let name = name.into();
+            let commands = actions.iter().map(Action::command).collect();
+            Self {
+                playback: CompositeCommand::new(name.as_str(), commands),
+                name,
+                actions,
+            }
+        }
+    }
+
+    impl Command for MacroCommand {
+        fn execute(&mut self, editor: &mut Editor) -> Result<bool, CommandError> {
+            self.playback.execute(editor)
+        }
+
+        fn undo(&mut self, editor: &mut Editor) {
+            self.playback.undo(editor);
+        }
+
+        fn redo(&mut self, editor: &mut Editor) {
+            self.playback.redo(editor);
+        }
+
+        fn edits(&self) -> Vec<Edit> {
+            self.playback.edits()
+        }
+
+        fn label(&self) -> String {
+            format!("Macro \"{}\"", self.name)
+        }
+
+        fn action(&self) -> Action {
+            Action::Macro {
+                name: self.name.clone(),
+                actions: self.actions.clone(),
+            }
+        }
+    }
+
+    #[derive(Debug)]
+    pub enum MacroError {
+        Io(io::Error),
+        Format(serde_json::Error),
+    }
+
+    impl fmt::Display for MacroError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                MacroError::Io(err) => write!(f, "cannot access the macro file: {}", err),
+                MacroError::Format(err) => write!(f, "invalid macro file: {}", err),
+            }
+        }
+    }
+
+    impl std::error::Error for MacroError {}
+
+    impl From<io::Error> for MacroError {
+        fn from(err: io::Error) -> Self {
+            MacroError::Io(err)
+        }
+    }
+
+    impl From<serde_json::Error> for MacroError {
+        fn from(err: serde_json::Error) -> Self {
+            MacroError::Format(err)
+        }
+    }
+
+    /// Records executed commands as named macros, and keeps macros to be
+    /// played later.
+    #[derive(Default)]
+    pub struct Recorder {
+        recording: Option<(String, Vec<Action>)>,
+        macros: BTreeMap<String, Vec<Action>>,
+    }
+
+    impl Recorder {
+        /// Reads macros saved as JSON, actions listed by macro name.
+        pub fn load(path: impl AsRef<Path>) -> Result<Self, MacroError> {
+            Ok(Self {
+                recording: None,
+                macros: serde_json::from_str(&fs::read_to_string(path)?)?,
+            })
+        }
+
+        pub fn save(&self, path: impl AsRef<Path>) -> Result<(), MacroError> {
+            fs::write(path, serde_json::to_string_pretty(&self.macros)?)?;
+            Ok(())
+        }
+
+        /// Starts recording a macro, dropping the one being recorded, if any.
+        pub fn start(&mut self, name: impl Into<String>) {
+            self.recording = Some((name.into(), vec![]));
+        }
+
+        pub fn is_recording(&self) -> bool {
+            self.recording.is_some()
+        }
+
+        /// Executes a command, and adds it to the macro being recorded, if
+        /// any, once it has done something: changed the document or copied
+        /// text. Commands that fail or do nothing would only get in the way
+        /// of playback, and macros can't run commands which can't be undone,
+        /// like opening a file.
+        pub fn execute(
+            &mut self,
+            editor: &mut Editor,
+            command: Box<dyn Command>,
+        ) -> Result<(), CommandError> {
+            let action = command.action();
+            let changed = editor.execute(command)?;
+
+            if let Some((_, actions)) = &mut self.recording {
+                if (changed || action == Action::Copy) && action.is_undoable() {
+                    actions.push(action);
+                }
+            }
+            Ok(())
+        }
+
+        /// Finishes recording, and keeps the macro in place of any other one
+        /// with the same name. Returns the name, or `None` if nothing has
+        /// been recorded.
+        pub fn stop(&mut self) -> Option<String> {
+            match self.recording.take() {
+                Some((name, actions)) if !actions.is_empty() => {
+                    self.macros.insert(name.clone(), actions);
+                    Some(name)
+                }
+                _ => None,
+            }
+        }
+
+        pub fn names(&self) -> impl Iterator<Item = &str> {
+            self.macros.keys().map(String::as_str)
+        }
+
+        /// Creates a command that plays a macro.
+        pub fn play(&self, name: &str) -> Option<MacroCommand> {
+            let actions = self.macros.get(name)?;
+            Some(MacroCommand::new(name, actions.clone()))
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::Recorder;
+        use crate::{
+            command::{
+                Action, Command, CopyCommand, InsertCommand, OpenFileCommand, PasteCommand,
+                SaveAsCommand,
+            },
+            editor::Editor,
+        };
+
+        fn greet() -> Vec<Action> {
+            vec![
+                Action::Insert {
+                    text: "hello".into(),
+                },
+                Action::Copy,
+                Action::Paste,
+            ]
+        }
+
+        #[test]
+        fn recording_keeps_commands_that_have_done_something() {
+            let dir = tempfile::tempdir().unwrap();
+            let mut editor = Editor::default();
+            let mut recorder = Recorder::default();
+            let commands: Vec<Box<dyn Command>> = vec![
+                Box::new(InsertCommand::new("hello")),
+                Box::new(CopyCommand),
+                Box::new(PasteCommand::default()),
+                Box::new(OpenFileCommand::new(dir.path().join("missing.txt"))),
+                Box::new(SaveAsCommand::new(dir.path().join("greeting.txt"))),
+            ];
+
+            recorder.start("greet");
+            for command in commands {
+                // Copying fails with nothing selected, and pasting does
+                // nothing with an empty clipboard.
+                let _ = recorder.execute(&mut editor, command);
+            }
+            editor.select(0..5);
+            for action in [Action::Copy, Action::Paste] {
+                recorder.execute(&mut editor, action.command()).unwrap();
+            }
+
+            assert_eq!(recorder.stop().as_deref(), Some("greet"));
+            let actions = match recorder.play("greet").unwrap().action() {
+                Action::Macro { actions, .. } => actions,
+                action => panic!("not a macro: {:?}", action),
+            };
+            assert_eq!(actions, greet());
+        }
+
+        #[test]
+        fn nothing_recorded_makes_no_macro() {
+            let mut editor = Editor::default();
+            let mut recorder = Recorder::default();
+
+            recorder.start("nothing");
+            let _ = recorder.execute(&mut editor, Box::new(CopyCommand));
+
+            assert_eq!(recorder.stop(), None);
+            assert_eq!(recorder.names().count(), 0);
+        }
+
+        #[test]
+        fn playback_is_undone_in_one_step() {
+            let mut recorder = Recorder::default();
+            let actions = vec![
+                Action::Insert {
+                    text: "hello".into(),
+                },
+                Action::Delete,
+                Action::Insert { text: "p!".into() },
+            ];
+            recorder.macros.insert("help".into(), actions);
+            let mut editor = Editor::default();
+
+            let playback = recorder.play("help").unwrap();
+            editor.execute(Box::new(playback)).unwrap();
+            assert_eq!(editor.text(), "hellp!");
+            assert_eq!(editor.history.undo_labels(), ["Macro \"help\""]);
+
+            assert!(editor.undo());
+            assert_eq!(editor.text(), "");
+            assert!(!editor.undo());
+        }
+
+        #[test]
+        fn failed_playback_leaves_the_document_as_it_was() {
+            let mut recorder = Recorder::default();
+            recorder.macros.insert("greet".into(), greet());
+            let mut editor = Editor::default();
+
+            // Nothing is selected to copy after the text is inserted.
+            let playback = recorder.play("greet").unwrap();
+            assert!(editor.execute(Box::new(playback)).is_err());
+
+            assert_eq!(editor.text(), "");
+            assert!(editor.history.undo_labels().is_empty());
+        }
+
+        #[test]
+        fn macros_are_saved_and_loaded() {
+            let dir = tempfile::tempdir().unwrap();
+            let path = dir.path().join("macros.json");
+            let mut recorder = Recorder::default();
+            recorder.macros.insert("greet".into(), greet());
+
+            recorder.save(&path).unwrap();
+            let loaded = Recorder::load(&path).unwrap();
+
+            assert_eq!(loaded.names().collect::<Vec<_>>(), ["greet"]);
+            assert_eq!(loaded.macros, recorder.macros);
+            assert!(!loaded.is_recording());
+        }
+    }
+}
+
+// Command registry
+pub mod registry {
+    use std::{collections::BTreeMap, fmt};
+
+    use crate::command::{
+        Command, CopyCommand, CutCommand, DeleteCommand, InsertCommand, OpenFileCommand,
+        PasteCommand, ReplaceAllCommand, SaveAsCommand, SaveCommand,
+    };
+
+    type Create = fn(&[String]) -> Box<dyn Command>;
+
+    /// A command that can be created by name, given the arguments it needs.
+    pub struct Registration {
+        pub name: &'static str,
+        pub title: &'static str,
+        /// Arguments to ask for before the command is created.
+        pub params: &'static [&'static str],
+        create: Create,
+    }
+
+    #[derive(Debug)]
+    pub enum RegistryError {
+        Unknown(String),
+        Arguments {
+            name: &'static str,
+            expected: usize,
+            given: usize,
+        },
+    }
+
+    impl fmt::Display for RegistryError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                RegistryError::Unknown(name) => write!(f, "unknown command `{}`", name),
+                RegistryError::Arguments {
+                    name,
+                    expected,
+                    given,
+                } => write!(
+                    f,
+                    "`{}` takes {} argument(s), {} given",
+                    name, expected, given
+                ),
+            }
+        }
+    }
+
+    impl std::error::Error for RegistryError {}
+
+    /// Commands by name, so frontends can offer every command without
+    /// knowing its type.
+    pub struct Registry {
+        commands: BTreeMap<&'static str, Registration>,
+    }
+
+    /// All editor commands.
+    impl Default for Registry {
+        fn default() -> Self {
+            let mut registry = Self {
+                commands: BTreeMap::new(),
+            };
+
+            registry.register("copy", "Copy", &[], |_| Box::new(CopyCommand));
+            registry.register("cut", "Cut", &[], |_| Box::new(CutCommand::default()));
+            registry.register("paste", "Paste", &[], |_| Box::new(PasteCommand::default()));
+            registry.register("delete", "Delete", &[], |_| {
+                Box::new(DeleteCommand::default())
+            });
+            registry.register("insert", "Insert text", &["text"], |args| {
+                Box::new(InsertCommand::new(args[0].as_str()))
+            });
+            registry.register(
+                "replace_all",
+                "Replace all",
+                &["find", "replace with"],
+                |args| Box::new(ReplaceAllCommand::new(args[0].as_str(), args[1].as_str())),
+            );
+            registry.register("open", "Open file", &["path"], |args| {
+                Box::new(OpenFileCommand::new(args[0].as_str()))
+            });
+            registry.register("save", "Save", &[], |_| Box::new(SaveCommand));
+            registry.register("save_as", "Save as", &["path"], |args| {
+                Box::new(SaveAsCommand::new(args[0].as_str()))
+            });
+
+            registry
+        }
+    }
+
+    impl Registry {
+        /// Adds a command in place of any other one with the same name.
+        pub fn register(
+            &mut self,
+            name: &'static str,
+            title: &'static str,
+            params: &'static [&'static str],
+            create: Create,
+        ) {
+            self.commands.insert(
+                name,
+                Registration {
+                    name,
+                    title,
+                    params,
+                    create,
+                },
+            );
+        }
+
+        pub fn get(&self, name: &str) -> Option<&Registration> {
+            self.commands.get(name)
+        }
+
+        pub fn create(
+            &self,
+            name: &str,
+            args: &[String],
+        ) -> Result<Box<dyn Command>, RegistryError> {
+            let registration = self
+                .get(name)
+                .ok_or_else(|| RegistryError::Unknown(name.into()))?;
+
+            if args.len() != registration.params.len() {
+                return Err(RegistryError::Arguments {
+                    name: registration.name,
+                    expected: registration.params.len(),
+                    given: args.len(),
+                });
+            }
+
+            Ok((registration.create)(args))
+        }
+
+        /// Commands with all characters of a query in their title or name,
+        /// in the same order, regardless of case.
+        pub fn search(&self, query: &str) -> Vec<&Registration> {
+            let query = query.to_lowercase();
+
+            self.commands
+                .values()
+                .filter(|registration| {
+                    [registration.title, registration.name]
+                        .iter()
+                        .any(|text| contains_in_order(&text.to_lowercase(), &query))
+                })
+                .collect()
+        }
+    }
+
+    fn contains_in_order(text: &str, query: &str) -> bool {
+        let mut chars = text.chars();
+        query
+            .chars()
+            .filter(|char| !char.is_whitespace())
+            .all(|wanted| chars.any(|char| char == wanted))
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::{Registry, RegistryError};
+
+        fn search(query: &str) -> Vec<&'static str> {
+            Registry::default()
+                .search(query)
+                .iter()
+                .map(|registration| registration.name)
+                .collect()
+        }
+
+        #[test]
+        fn palette_finds_characters_in_order_regardless_of_case() {
+            assert_eq!(search("sa"), ["save", "save_as"]);
+            assert_eq!(search("S A"), ["save", "save_as"]);
+            assert_eq!(search("rpa"), ["replace_all"]);
+            assert_eq!(search("file"), ["open"]);
+            assert!(search("as s").is_empty());
+            assert!(search("zz").is_empty());
+        }
+
+        #[test]
+        fn empty_query_finds_every_command_by_name() {
+            assert_eq!(
+                search(""),
+                [
+                    "copy",
+                    "cut",
+                    "delete",
+                    "insert",
+                    "open",
+                    "paste",
+                    "replace_all",
+                    "save",
+                    "save_as"
+                ]
+            );
+        }
+
+        #[test]
+        fn commands_are_created_with_their_arguments() {
+            let registry = Registry::default();
+
+            let command = registry.create("insert", &["hi".into()]).unwrap();
+            assert_eq!(command.label(), "Typing \"hi\"");
+
+            assert!(matches!(
+                registry.create("insert", &[]),
+                Err(RegistryError::Arguments {
+                    name: "insert",
+                    expected: 1,
+                    given: 0,
+                })
+            ));
+            assert!(matches!(
+                registry.create("copy", &["x".into()]),
+                Err(RegistryError::Arguments { given: 1, .. })
+            ));
+            assert!(matches!(
+                registry.create("undo", &[]),
+                Err(RegistryError::Unknown(name)) if name == "undo"
+            ));
+        }
+    }
+}
+
+// Key bindings
+pub mod keymap {
+    use std::{collections::BTreeMap, fmt, fs, io, path::Path, str::FromStr};
+
+    use crate::registry::Registry;
+
+    /// A key, possibly pressed with a modifier, e.g. `ctrl+s` or `f5`.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+    pub enum Shortcut {
+        Char(char),
+        Ctrl(char),
+        Alt(char),
+        F(u8),
+    }
+
+    impl FromStr for Shortcut {
+        type Err = ();
+
+        fn from_str(key: &str) -> Result<Self, Self::Err> {
+            fn single(key: &str) -> Result<char, ()> {
+                let mut chars = key.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(char), None) => Ok(char),
+                    _ => Err(()),
+                }
+            }
+
+            // A lone `+` is the plus key rather than a modifier separator.
+            if let Some((modifier, key)) = key.split_once('+').filter(|_| key != "+") {
+                return match modifier.to_lowercase().as_str() {
+                    "ctrl" => single(key).map(|char| Shortcut::Ctrl(char.to_ascii_lowercase())),
+                    "alt" => single(key).map(Shortcut::Alt),
+                    _ => Err(()),
+                };
+            }
+
+            match key.strip_prefix(['f', 'F']).map(str::parse) {
+                Some(Ok(number @ 1..=12)) => Ok(Shortcut::F(number)),
+                _ => single(key).map(Shortcut::Char),
+            }
+        }
+    }
+
+    impl fmt::Display for Shortcut {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                Shortcut::Char(char) => write!(f, "{}", char),
+                Shortcut::Ctrl(char) => write!(f, "Ctrl+{}", char.to_ascii_uppercase()),
+                Shortcut::Alt(char) => write!(f, "Alt+{}", char),
+                Shortcut::F(number) => write!(f, "F{}", number),
+            }
+        }
+    }
+
+    #[derive(Debug)]
+    pub enum KeymapError {
+        Io(io::Error),
+        Syntax {
+            line: usize,
+        },
+        UnknownKey {
+            line: usize,
+            key: String,
+        },
+        /// A key is bound on an earlier line already.
+        Duplicate {
+            line: usize,
+            key: Shortcut,
+        },
+        /// A key is bound to a command nobody knows.
+        UnknownCommand {
+            key: Shortcut,
+            command: String,
+        },
+    }
+
+    impl fmt::Display for KeymapError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                KeymapError::Io(err) => write!(f, "cannot read key bindings: {}", err),
+                KeymapError::Syntax { line } => {
+                    write!(f, "line {}: expected `key = command`", line)
+                }
+                KeymapError::UnknownKey { line, key } => {
+                    write!(f, "line {}: unknown key `{}`", line, key)
+                }
+                KeymapError::Duplicate { line, key } => {
+                    write!(f, "line {}: {} is bound already", line, key)
+                }
+                KeymapError::UnknownCommand { key, command } => {
+                    write!(f, "{} is bound to unknown command `{}`", key, command)
+                }
+            }
+        }
+    }
+
+    impl std::error::Error for KeymapError {}
+
+    impl From<io::Error> for KeymapError {
+        fn from(err: io::Error) -> Self {
+            KeymapError::Io(err)
+        }
+    }
+
+    /// Command names bound to keys.
+    #[derive(Default)]
+    pub struct Keymap {
+        bindings: BTreeMap<Shortcut, String>,
+    }
+
+    impl Keymap {
+        pub fn load(path: impl AsRef<Path>) -> Result<Self, KeymapError> {
+            Self::from_config(&fs::read_to_string(path)?)
+        }
+
+        /// Reads `key = command` bindings, one per line, each key bound
+        /// once. Empty lines and lines starting with `#` are ignored.
+        pub fn from_config(config: &str) -> Result<Self, KeymapError> {
+            let mut bindings = BTreeMap::new();
+
+            for (number, line) in config.lines().enumerate() {
+                let line = line.trim();
+                if line.is_empty() || line.starts_with('#') {
+                    continue;
+                }
+
+                let (key, command) = match line.split_once('=') {
+                    Some((key, command)) if !command.trim().is_empty() => {
+                        (key.trim(), command.trim())
+                    }
+                    _ => return Err(KeymapError::Syntax { line: number + 1 }),
+                };
+
+                let shortcut = key.parse().map_err(|()| KeymapError::UnknownKey {
+                    line: number + 1,
+                    key: key.into(),
+                })?;
+                if bindings.insert(shortcut, command.to_string()).is_some() {
+                    return Err(KeymapError::Duplicate {
+                        line: number + 1,
+                        key: shortcut,
+                    });
+                }
+            }
+
+            Ok(Self { bindings })
+        }
+
+        /// Drops bindings to commands that are neither registered nor one
+        /// of the actions a frontend runs itself, e.g. `undo`, and tells
+        /// which they were.
+        pub fn remove_unknown(
+            &mut self,
+            registry: &Registry,
+            actions: &[&str],
+        ) -> Vec<KeymapError> {
+            let mut unknown = vec![];
+            self.bindings.retain(|&key, command| {
+                let known = actions.contains(&command.as_str()) || registry.get(command).is_some();
+                if !known {
+                    unknown.push(KeymapError::UnknownCommand {
+                        key,
+                        command: command.clone(),
+                    });
+                }
+                known
+            });
+            unknown
+        }
+
+        pub fn bindings(&self) -> impl Iterator<Item = (Shortcut, &str)> {
+            self.bindings
+                .iter()
+                .map(|(&shortcut, command)| (shortcut, command.as_str()))
+        }
+
+        /// The first key bound to a command, if any.
+        pub fn shortcut(&self, command: &str) -> Option<Shortcut> {
+            self.bindings()
+                .find(|&(_, bound)| bound == command)
+                .map(|(shortcut, _)| shortcut)
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::{Keymap, KeymapError, Shortcut};
+        use crate::registry::Registry;
+
+        #[test]
+        fn keys_are_parsed() {
+            for (key, shortcut) in [
+                ("a", Shortcut::Char('a')),
+                ("+", Shortcut::Char('+')),
+                ("f", Shortcut::Char('f')),
+                ("ctrl+s", Shortcut::Ctrl('s')),
+                ("Ctrl+S", Shortcut::Ctrl('s')),
+                ("alt+X", Shortcut::Alt('X')),
+                ("f1", Shortcut::F(1)),
+                ("F12", Shortcut::F(12)),
+            ] {
+                assert_eq!(key.parse(), Ok(shortcut), "{}", key);
+            }
+        }
+
+        #[test]
+        fn unknown_keys_are_refused() {
+            for key in [
+                "", "ab", "f13", "f0", "shift+a", "ctrl+", "ctrl+ab", "alt+f5",
+            ] {
+                assert_eq!(key.parse::<Shortcut>(), Err(()), "{}", key);
+            }
+        }
+
+        #[test]
+        fn shortcuts_are_shown_as_in_menus() {
+            assert_eq!(Shortcut::Ctrl('s').to_string(), "Ctrl+S");
+            assert_eq!(Shortcut::Alt('x').to_string(), "Alt+x");
+            assert_eq!(Shortcut::F(5).to_string(), "F5");
+        }
+
+        #[test]
+        fn bindings_are_read_skipping_comments_and_blank_lines() {
+            let keymap = Keymap::from_config(
+                "# Saving\n\n  ctrl+s = save\nalt+s=save_as\n   \nf1 = palette\n",
+            )
+            .unwrap();
+
+            let bindings: Vec<_> = keymap.bindings().collect();
+            assert_eq!(
+                bindings,
+                [
+                    (Shortcut::Ctrl('s'), "save"),
+                    (Shortcut::Alt('s'), "save_as"),
+                    (Shortcut::F(1), "palette"),
+                ]
+            );
+            assert_eq!(keymap.shortcut("save"), Some(Shortcut::Ctrl('s')));
+            assert_eq!(keymap.shortcut("quit"), None);
+        }
+
+        #[test]
+        fn bad_bindings_are_refused_with_their_line() {
+            let error = |config| Keymap::from_config(config).err().unwrap();
+
+            assert!(matches!(
+                error("ctrl+s = save\nshift+s = save_as"),
+                KeymapError::UnknownKey { line: 2, key } if key == "shift+s"
+            ));
+            assert!(matches!(
+                error("# no command\nctrl+s ="),
+                KeymapError::Syntax { line: 2 }
+            ));
+            assert!(matches!(
+                error("ctrl+s save"),
+                KeymapError::Syntax { line: 1 }
+            ));
+            assert!(matches!(
+                error("ctrl+s = save\n\nCtrl+S = save_as"),
+                KeymapError::Duplicate {
+                    line: 3,
+                    key: Shortcut::Ctrl('s'),
+                }
+            ));
+        }
+
+        #[test]
+        fn bindings_to_unknown_commands_are_removed() {
+            let mut keymap =
+                Keymap::from_config("ctrl+s = save\nctrl+z = undo\nctrl+w = close").unwrap();
+
+            let unknown = keymap.remove_unknown(&Registry::default(), &["undo"]);
+
+            assert!(matches!(
+                unknown.as_slice(),
+                [KeymapError::UnknownCommand { key: Shortcut::Ctrl('w'), command }]
+                    if command == "close"
+            ));
+            assert_eq!(
+                unknown[0].to_string(),
+                "Ctrl+W is bound to unknown command `close`"
+            );
+            let bound: Vec<_> = keymap.bindings().map(|(_, command)| command).collect();
+            assert_eq!(bound, ["save", "undo"]);
+        }
+    }
+}
+
+// Deferred and remote execution
+pub mod remote {
+    use std::{
+        env, fs,
+        io::{self, BufRead, BufReader, Write},
+        os::unix::{
+            fs::{DirBuilderExt, MetadataExt, PermissionsExt},
+            net::{UnixListener, UnixStream},
+        },
+        path::{Path, PathBuf},
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            mpsc, Arc,
+        },
+        thread::{self, JoinHandle},
+    };
+
+    use serde::{Deserialize, Serialize};
+
+    use crate::{command::Action, editor::Editor};
+
+    /// Where a running editor listens for commands of other processes: the
+    /// user's runtime directory, or a directory of the user's own in the
+    /// shared temporary one.
+    pub fn socket_path() -> io::Result<PathBuf> {
+        let dir = match env::var_os("XDG_RUNTIME_DIR") {
+            Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => env::temp_dir().join(format!("command-editor-{}", uid())),
+        };
+        Ok(private_dir(dir)?.join("command-editor.sock"))
+    }
+
+    /// Creates a directory only the user can enter, or makes sure an
+    /// existing one is so. Submitted actions can open and save files, so
+    /// nobody else may get to the socket, or put their own in its place.
+    fn private_dir(dir: PathBuf) -> io::Result<PathBuf> {
+        match fs::DirBuilder::new().mode(0o700).create(&dir) {
+            Err(err) if err.kind() != io::ErrorKind::AlreadyExists => return Err(err),
+            _ => {}
+        }
+
+        let metadata = fs::symlink_metadata(&dir)?;
+        if !metadata.is_dir() || metadata.uid() != uid() || metadata.mode() & 0o077 != 0 {
+            let message = format!("{} is not a private directory", dir.display());
+            return Err(io::Error::new(io::ErrorKind::PermissionDenied, message));
+        }
+        Ok(dir)
+    }
+
+    fn uid() -> u32 {
+        // SAFETY: getuid has no preconditions and always succeeds.
+        unsafe { libc::getuid() }
+    }
+
+    /// The outcome of a queued action, reported back to its submitter.
+    #[derive(Debug, Serialize, Deserialize)]
+    #[serde(tag = "status", rename_all = "snake_case")]
+    pub enum Response {
+        Done { text: String, dirty: bool },
+        Failed { error: String },
+    }
+
+    /// An action waiting to be executed, and where to send its outcome.
+    struct Job {
+        action: Action,
+        reply: mpsc::Sender<Response>,
+    }
+
+    /// Actions deferred until the editor is free to run them. The queue
+    /// stays with the editor, while submitters may live on other threads.
+    pub struct Queue {
+        sender: mpsc::Sender<Job>,
+        jobs: mpsc::Receiver<Job>,
+    }
+
+    impl Default for Queue {
+        fn default() -> Self {
+            let (sender, jobs) = mpsc::channel();
+            Self { sender, jobs }
+        }
+    }
+
+    impl Queue {
+        /// Creates a submitter that calls `wake` after queueing an action,
+        /// so the owner of the editor knows it's time to run the queue.
+        pub fn submitter(&self, wake: impl Fn() + Send + Sync + 'static) -> Submitter {
+            Submitter {
+                sender: self.sender.clone(),
+                wake: Arc::new(wake),
+            }
+        }
+
+        /// Executes all queued actions in the order they were submitted.
+        pub fn run(&self, editor: &mut Editor) {
+            for job in self.jobs.try_iter() {
+                let response = match editor.execute(job.action.command()) {
+                    Ok(_) => Response::Done {
+                        text: editor.text().to_string(),
+                        dirty: editor.is_dirty(),
+                    },
+                    Err(err) => Response::Failed {
+                        error: err.to_string(),
+                    },
+                };
+                // The submitter may have stopped waiting, that's fine.
+                let _ = job.reply.send(response);
+            }
+        }
+    }
+
+    #[derive(Clone)]
+    pub struct Submitter {
+        sender: mpsc::Sender<Job>,
+        wake: Arc<dyn Fn() + Send + Sync>,
+    }
+
+    impl Submitter {
+        /// Queues an action and waits until the editor has run it.
+        pub fn submit(&self, action: Action) -> Response {
+            let (reply, response) = mpsc::channel();
+            if self.sender.send(Job { action, reply }).is_ok() {
+                (self.wake)();
+            }
+            response.recv().unwrap_or_else(|_| Response::Failed {
+                error: "the editor is closed".into(),
+            })
+        }
+    }
+
+    /// Accepts actions from other processes on a Unix socket, one JSON
+    /// action per line, and answers each with a JSON response line.
+    /// The socket file is removed, and the thread accepting connections is
+    /// stopped, when the server is dropped.
+    pub struct Server {
+        path: PathBuf,
+        stop: Arc<AtomicBool>,
+        accept: Option<JoinHandle<()>>,
+    }
+
+    impl Server {
+        pub fn start(path: impl Into<PathBuf>, submitter: Submitter) -> io::Result<Self> {
+            let path = path.into();
+
+            // A socket left by an editor that has crashed is replaced,
+            // but one that still answers belongs to a running editor.
+            if UnixStream::connect(&path).is_ok() {
+                let message = format!("{} is used by another editor", path.display());
+                return Err(io::Error::new(io::ErrorKind::AddrInUse, message));
+            }
+            let _ = fs::remove_file(&path);
+
+            // The socket is in a private directory already, this only keeps
+            // it private if it's moved out.
+            let listener = UnixListener::bind(&path)?;
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o600))?;
+
+            let stop = Arc::new(AtomicBool::new(false));
+            let stopped = stop.clone();
+            let accept = thread::spawn(move || {
+                for stream in listener.incoming().flatten() {
+                    if stopped.load(Ordering::Relaxed) {
+                        break;
+                    }
+
+                    let submitter = submitter.clone();
+                    thread::spawn(move || {
+                        // A client that has hung up needs no answer.
+                        let _ = serve(stream, &submitter);
+                    });
+                }
+            });
+
+            Ok(Self {
+                path,
+                stop,
+                accept: Some(accept),
+            })
+        }
+    }
+
+    impl Drop for Server {
+        fn drop(&mut self) {
+            self.stop.store(true, Ordering::Relaxed);
+            // Accepting waits for a client, so the server connects to itself
+            // to wake the thread up. If it can't, the thread is left behind
+            // rather than waited for forever.
+            if UnixStream::connect(&self.path).is_ok() {
+                if let Some(accept) = self.accept.take() {
+                    let _ = accept.join();
+                }
+            }
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+
+    fn serve(stream: UnixStream, submitter: &Submitter) -> io::Result<()> {
+        let mut writer = stream.try_clone()?;
+
+        for line in BufReader::new(stream).lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let response = match serde_json::from_str(&line) {
+                Ok(action) => submitter.submit(action),
+                Err(err) => Response::Failed {
+                    error: format!("invalid command: {}", err),
+                },
+            };
+            writeln!(writer, "{}", serde_json::to_string(&response)?)?;
+        }
+
+        Ok(())
+    }
+
+    /// Sends an action to the editor listening at `path`, and waits for
+    /// the response.
+    pub fn submit(path: impl AsRef<Path>, action: &Action) -> io::Result<Response> {
+        let mut stream = UnixStream::connect(path)?;
+        writeln!(stream, "{}", serde_json::to_string(action)?)?;
+
+        let mut line = String::new();
+        BufReader::new(stream).read_line(&mut line)?;
+        Ok(serde_json::from_str(&line)?)
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use std::{
+            fs,
+            io::{BufRead, BufReader, Write},
+            os::unix::{fs::PermissionsExt, net::UnixStream},
+            path::PathBuf,
+            sync::{mpsc, Arc},
+            thread,
+        };
+
+        use super::{private_dir, submit, Queue, Response, Server};
+        use crate::{command::Action, editor::Editor};
+
+        fn insert(text: &str) -> Action {
+            Action::Insert { text: text.into() }
+        }
+
+        /// Runs queued actions whenever the queue is woken up, until
+        /// `count` of them have been woken for.
+        fn run_queue(queue: &Queue, woken: &mpsc::Receiver<()>, count: usize) -> Editor {
+            let mut editor = Editor::default();
+            for _ in 0..count {
+                woken.recv().unwrap();
+                queue.run(&mut editor);
+            }
+            editor
+        }
+
+        #[test]
+        fn queued_actions_run_on_the_editor_in_order() {
+            let queue = Queue::default();
+            let (wake, woken) = mpsc::channel();
+            let submitter = queue.submitter(move || wake.send(()).unwrap());
+
+            let client = thread::spawn(move || {
+                let first = submitter.submit(insert("hello"));
+                let second = submitter.submit(insert(" world"));
+                let third = submitter.submit(Action::Copy);
+                (first, second, third)
+            });
+            let editor = run_queue(&queue, &woken, 3);
+
+            let (first, second, third) = client.join().unwrap();
+            assert!(matches!(first, Response::Done { text, dirty: true } if text == "hello"));
+            assert!(matches!(second, Response::Done { text, .. } if text == "hello world"));
+            assert!(matches!(third, Response::Failed { error } if error == "nothing is selected"));
+            assert_eq!(editor.history.undo_labels().len(), 1);
+        }
+
+        #[test]
+        fn actions_fail_once_the_editor_is_closed() {
+            let queue = Queue::default();
+            let submitter = queue.submitter(|| {});
+            drop(queue);
+
+            let response = submitter.submit(insert("hello"));
+
+            assert!(
+                matches!(response, Response::Failed { error } if error == "the editor is closed")
+            );
+        }
+
+        #[test]
+        fn server_answers_actions_from_other_processes() {
+            let dir = tempfile::tempdir().unwrap();
+            let path = dir.path().join("editor.sock");
+            let queue = Queue::default();
+            let (wake, woken) = mpsc::channel();
+            let wake = std::sync::Mutex::new(wake);
+            let server = Server::start(
+                &path,
+                queue.submitter(move || {
+                    wake.lock().unwrap().send(()).unwrap();
+                }),
+            )
+            .unwrap();
+
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+            let taken = Server::start(&path, queue.submitter(|| {}));
+            assert_eq!(taken.err().unwrap().kind(), std::io::ErrorKind::AddrInUse);
+
+            let client_path = path.clone();
+            let client = thread::spawn(move || {
+                let response = submit(&client_path, &insert("hello")).unwrap();
+
+                let mut stream = UnixStream::connect(&client_path).unwrap();
+                writeln!(stream, "{{\"command\": \"fly\"}}").unwrap();
+                let mut line = String::new();
+                BufReader::new(stream).read_line(&mut line).unwrap();
+                (response, line)
+            });
+            let editor = run_queue(&queue, &woken, 1);
+
+            let (response, line) = client.join().unwrap();
+            assert!(matches!(response, Response::Done { text, .. } if text == "hello"));
+            assert!(line.starts_with(r#"{"status":"failed","error":"invalid command: "#));
+            assert_eq!(editor.text(), "hello");
+
+            drop(server);
+            assert!(!path.exists());
+        }
+
+        #[test]
+        fn dropped_server_stops_accepting() {
+            let dir = tempfile::tempdir().unwrap();
+            let path = dir.path().join("editor.sock");
+            let queue = Queue::default();
+            // Held by the submitter of the thread accepting connections.
+            let alive = Arc::new(());
+            let held = alive.clone();
+
+            let submitter = queue.submitter(move || {
+                let _ = &held;
+            });
+
+            let server = Server::start(&path, submitter).unwrap();
+            assert_eq!(Arc::strong_count(&alive), 2);
+            drop(server);
+
+            assert_eq!(Arc::strong_count(&alive), 1);
+            assert!(!path.exists());
+        }
+
+        #[test]
+        fn server_replaces_a_socket_left_behind() {
+            let dir = tempfile::tempdir().unwrap();
+            let path = dir.path().join("editor.sock");
+            fs::write(&path, "").unwrap();
+            let queue = Queue::default();
+
+            let server = Server::start(&path, queue.submitter(|| {}));
+
+            assert!(server.is_ok());
+        }
+
+        #[test]
+        fn socket_directory_is_private() {
+            let dir = tempfile::tempdir().unwrap();
+
+            let created = private_dir(dir.path().join("editor")).unwrap();
+            let mode = fs::metadata(&created).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o700);
+            assert!(private_dir(created).is_ok());
+
+            let shared = dir.path().join("shared");
+            fs::create_dir(&shared).unwrap();
+            fs::set_permissions(&shared, fs::Permissions::from_mode(0o755)).unwrap();
+            assert!(private_dir(shared).is_err());
+
+            let link = dir.path().join("link");
+            std::os::unix::fs::symlink(dir.path().join("editor"), &link).unwrap();
+            assert!(private_dir(link).is_err());
+
+            assert!(private_dir(PathBuf::from("/")).is_err());
+        }
+    }
+}
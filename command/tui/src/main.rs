@@ -0,0 +1,394 @@
+/*
+Text editor on top of the command crate, with cursive as the frontend.
+
+Each button, palette entry or key binding runs a command on the editor model;
+the model itself doesn't depend on any UI, so it is built and tested without
+a terminal.
+*/
+
+use std::{io, ops::Range};
+
+use cursive::{
+    event::{Event, Key},
+    traits::Nameable,
+    views::{Dialog, EditView, LinearLayout, SelectView, TextView},
+    Cursive,
+};
+
+use command::{
+    command::{Command, DeleteCommand, InsertCommand},
+    editor::Editor,
+    keymap::{Keymap, Shortcut},
+    macros::{MacroError, Recorder},
+    registry::Registry,
+    remote::{self, Queue, Server},
+};
+
+const MACROS: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/macros.json");
+const KEYS: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/keys.conf");
+
+/// What can be bound to keys besides commands.
+const ACTIONS: [&str; 5] = ["palette", "undo", "redo", "select_all", "quit"];
+
+/// An application context to be passed into visual component callbacks.
+/// It contains an editor with its history of commands, macros, commands
+/// available by name, and commands submitted by other processes.
+#[derive(Default)]
+struct AppContext {
+    editor: Editor,
+    macros: Recorder,
+    registry: Registry,
+    keymap: Keymap,
+    queue: Queue,
+}
+
+fn main() {
+    let mut app = cursive::default();
+    let mut context = AppContext::default();
+    let mut problems = vec![];
+
+    match Recorder::load(MACROS) {
+        Ok(macros) => context.macros = macros,
+        Err(MacroError::Io(err)) if err.kind() == io::ErrorKind::NotFound => {}
+        Err(err) => problems.push(err.to_string()),
+    }
+
+    match Keymap::load(KEYS) {
+        Ok(keymap) => context.keymap = keymap,
+        Err(err) => problems.push(err.to_string()),
+    }
+
+    let unknown = context.keymap.remove_unknown(&context.registry, &ACTIONS);
+    problems.extend(unknown.iter().map(ToString::to_string));
+    for (shortcut, name) in context.keymap.bindings() {
+        let name = name.to_string();
+        app.set_global_callback(event(shortcut), move |s| run_action(s, &name));
+    }
+
+    // Submitted actions wait until the UI thread is free to run them.
+    let sink = app.cb_sink().clone();
+    let submitter = context.queue.submitter(move || {
+        let _ = sink.send(Box::new(run_queued));
+    });
+    let _server = match remote::socket_path().and_then(|path| Server::start(path, submitter)) {
+        Ok(server) => Some(server),
+        Err(err) => {
+            problems.push(format!("Remote commands are unavailable: {}", err));
+            None
+        }
+    };
+
+    app.set_user_data(context);
+    app.add_layer(
+        Dialog::around(EditView::default().on_edit(on_edit).with_name("Editor"))
+            .title("Untitled")
+            .button("Commands", show_palette)
+            .button("Undo", |s| run_action(s, "undo"))
+            .button("Redo", |s| run_action(s, "redo"))
+            .button("History", show_history)
+            .button("Record", record)
+            .button("Macros", show_macros)
+            .button("Quit", |s| s.quit())
+            .with_name("Main"),
+    );
+
+    for problem in problems {
+        app.add_layer(Dialog::info(problem));
+    }
+
+    app.run();
+}
+
+fn event(shortcut: Shortcut) -> Event {
+    match shortcut {
+        Shortcut::Char(char) => Event::Char(char),
+        Shortcut::Ctrl(char) => Event::CtrlChar(char),
+        Shortcut::Alt(char) => Event::AltChar(char),
+        Shortcut::F(number) => Event::Key(Key::from_f(number)),
+    }
+}
+
+/// Runs an editor action or a command by its name.
+fn run_action(app: &mut Cursive, name: &str) {
+    match name {
+        "palette" => show_palette(app),
+        "undo" => with_editor(app, |c| c.editor.undo()),
+        "redo" => with_editor(app, |c| c.editor.redo()),
+        "select_all" => with_editor(app, |c| c.editor.select(0..c.editor.text().len())),
+        "quit" => app.quit(),
+        name => run_command(app, name),
+    }
+}
+
+/// Creates a registered command and executes it, asking for its
+/// arguments first if it needs any.
+fn run_command(app: &mut Cursive, name: &str) {
+    let registration = app
+        .with_user_data(|context: &mut AppContext| {
+            let registration = context.registry.get(name)?;
+            Some((registration.title, registration.params))
+        })
+        .flatten();
+
+    match registration {
+        Some((_, [])) => create(app, name, &[]),
+        Some((title, params)) => {
+            let mut fields = LinearLayout::vertical();
+            for &param in params {
+                fields.add_child(TextView::new(param));
+                fields.add_child(EditView::default().with_name(param));
+            }
+
+            let name = name.to_string();
+            app.add_layer(
+                Dialog::around(fields)
+                    .title(title)
+                    .button("Ok", move |s| {
+                        let args: Vec<String> = params
+                            .iter()
+                            .map(|&param| {
+                                s.find_name::<EditView>(param)
+                                    .unwrap()
+                                    .get_content()
+                                    .to_string()
+                            })
+                            .collect();
+                        s.pop_layer();
+                        create(s, &name, &args);
+                    })
+                    .dismiss_button("Cancel"),
+            );
+        }
+        None => app.add_layer(Dialog::info(format!("Unknown command `{}`", name))),
+    }
+}
+
+fn create(app: &mut Cursive, name: &str, args: &[String]) {
+    let command = app
+        .with_user_data(|context: &mut AppContext| context.registry.create(name, args))
+        .expect("an application context");
+
+    match command {
+        Ok(command) => execute(app, command),
+        Err(err) => app.add_layer(Dialog::info(err.to_string())),
+    }
+}
+
+/// Lists commands matching what is typed, with their keys.
+fn show_palette(app: &mut Cursive) {
+    let mut matches = SelectView::new();
+    matches.add_all(palette(app, ""));
+    matches.set_on_submit(|s, name: &&'static str| {
+        s.pop_layer();
+        run_action(s, name);
+    });
+
+    let query = EditView::default()
+        .on_edit(|s, query, _| {
+            let items = palette(s, query);
+            s.call_on_name("Matches", |matches: &mut SelectView<&'static str>| {
+                matches.clear();
+                matches.add_all(items);
+            });
+        })
+        .on_submit(|s, _| {
+            let name = s
+                .call_on_name("Matches", |matches: &mut SelectView<&'static str>| {
+                    matches.selection()
+                })
+                .flatten();
+            if let Some(name) = name {
+                s.pop_layer();
+                run_action(s, *name);
+            }
+        });
+
+    app.add_layer(
+        Dialog::around(
+            LinearLayout::vertical()
+                .child(query)
+                .child(matches.with_name("Matches")),
+        )
+        .title("Commands")
+        .dismiss_button("Close"),
+    );
+}
+
+fn palette(app: &mut Cursive, query: &str) -> Vec<(String, &'static str)> {
+    app.with_user_data(|context: &mut AppContext| {
+        context
+            .registry
+            .search(query)
+            .into_iter()
+            .map(|registration| {
+                let label = match context.keymap.shortcut(registration.name) {
+                    Some(shortcut) => format!("{:<16}{}", registration.title, shortcut),
+                    None => registration.title.to_string(),
+                };
+                (label, registration.name)
+            })
+            .collect()
+    })
+    .unwrap_or_default()
+}
+
+/// Shows the file name with a `*` if there are unsaved changes, and
+/// the message about the latest command, if any.
+fn refresh(app: &mut Cursive) {
+    let Some((title, status)) = app.with_user_data(|context: &mut AppContext| {
+        let editor = &mut context.editor;
+        let name = editor
+            .path()
+            .and_then(|path| path.file_name())
+            .map_or("Untitled".into(), |name| name.to_string_lossy());
+        let dirty = if editor.is_dirty() { "*" } else { "" };
+        (format!("{}{}", name, dirty), editor.status.take())
+    }) else {
+        return;
+    };
+
+    app.call_on_name("Main", |dialog: &mut Dialog| dialog.set_title(title));
+    if let Some(status) = status {
+        app.add_layer(Dialog::info(status));
+    }
+}
+
+/// Runs an action on the editor, at the cursor of the edit view unless
+/// something is selected, and shows the text and the cursor after.
+fn with_editor<T>(app: &mut Cursive, action: impl FnOnce(&mut AppContext) -> T) {
+    let mut view = app.find_name::<EditView>("Editor").unwrap();
+
+    app.with_user_data(|context: &mut AppContext| {
+        if context.editor.selection().is_empty() {
+            context.editor.set_cursor(view.get_cursor());
+        }
+        action(context);
+        view.set_content(context.editor.text());
+        view.set_cursor(context.editor.cursor());
+    });
+
+    refresh(app);
+}
+
+/// Executes a command and then pushes it to history, and to the macro
+/// being recorded.
+fn execute(app: &mut Cursive, command: Box<dyn Command>) {
+    with_editor(app, |context| {
+        if let Err(err) = context.macros.execute(&mut context.editor, command) {
+            context.editor.status = Some(err.to_string());
+        }
+    });
+}
+
+fn run_queued(app: &mut Cursive) {
+    with_editor(app, |context| context.queue.run(&mut context.editor));
+}
+
+/// Turns an edit made in the view into a command: typing replaces
+/// the changed range, removing text deletes it.
+fn on_edit(app: &mut Cursive, content: &str, _cursor: usize) {
+    app.with_user_data(|context: &mut AppContext| {
+        let (range, inserted) = changed(context.editor.text(), content);
+        if range.is_empty() && inserted.is_empty() {
+            return;
+        }
+
+        context.editor.select(range);
+        let command: Box<dyn Command> = if inserted.is_empty() {
+            Box::new(DeleteCommand::default())
+        } else {
+            Box::new(InsertCommand::new(inserted))
+        };
+        if let Err(err) = context.macros.execute(&mut context.editor, command) {
+            context.editor.status = Some(err.to_string());
+        }
+    });
+
+    refresh(app);
+}
+
+/// Finds the range of `old` text replaced with a part of `new` one.
+fn changed<'a>(old: &str, new: &'a str) -> (Range<usize>, &'a str) {
+    let prefix = old
+        .char_indices()
+        .zip(new.chars())
+        .find(|((_, old), new)| old != new)
+        .map_or(old.len().min(new.len()), |((at, _), _)| at);
+
+    let suffix: usize = old[prefix..]
+        .chars()
+        .rev()
+        .zip(new[prefix..].chars().rev())
+        .take_while(|(old, new)| old == new)
+        .map(|(char, _)| char.len_utf8())
+        .sum();
+
+    (prefix..old.len() - suffix, &new[prefix..new.len() - suffix])
+}
+
+/// Lists commands that can be undone and redone, the nearest first.
+fn show_history(app: &mut Cursive) {
+    let text = app
+        .with_user_data(|context: &mut AppContext| {
+            format!(
+                "Undo: {}\nRedo: {}",
+                context.editor.history.undo_labels().join(", "),
+                context.editor.history.redo_labels().join(", ")
+            )
+        })
+        .unwrap_or_default();
+
+    app.add_layer(Dialog::info(text).title("History"));
+}
+
+/// Asks for a name to start recording a macro, or stops recording and
+/// saves macros.
+fn record(app: &mut Cursive) {
+    let stopped = app.with_user_data(|context: &mut AppContext| {
+        if !context.macros.is_recording() {
+            return None;
+        }
+        context.macros.stop();
+        Some(context.macros.save(MACROS))
+    });
+
+    match stopped.flatten() {
+        Some(Ok(())) => {}
+        Some(Err(err)) => app.add_layer(Dialog::info(err.to_string()).title("Macros")),
+        None => app.add_layer(
+            Dialog::around(EditView::default().with_name("Macro"))
+                .title("Record a macro")
+                .button("Start", |s| {
+                    let name = s.find_name::<EditView>("Macro").unwrap().get_content();
+                    s.pop_layer();
+                    s.with_user_data(|context: &mut AppContext| {
+                        context.macros.start(name.as_str())
+                    });
+                })
+                .dismiss_button("Cancel"),
+        ),
+    }
+}
+
+/// Lists macros to choose one to play.
+fn show_macros(app: &mut Cursive) {
+    let names: Vec<String> = app
+        .with_user_data(|context: &mut AppContext| {
+            context.macros.names().map(String::from).collect()
+        })
+        .unwrap_or_default();
+
+    let mut list = SelectView::new();
+    list.add_all_str(names);
+    list.set_on_submit(|s, name: &str| {
+        s.pop_layer();
+        let command = s
+            .with_user_data(|context: &mut AppContext| context.macros.play(name))
+            .flatten();
+        if let Some(command) = command {
+            execute(s, Box::new(command));
+        }
+    });
+
+    app.add_layer(Dialog::around(list).title("Macros").dismiss_button("Close"));
+}
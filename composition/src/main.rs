@@ -3,88 +3,1652 @@ Files and Folders
 
 File and Directory are both of trait Component with a single search method. For
 a file, it will just look into the contents of the file; for a folder, it will go
-through all files of that folder to find that keyword.
+through all files of that folder to find that query.
+
+A query is a literal, case-insensitive, whole-word, fuzzy or regular expression
+pattern, and may only look into files of certain names or extensions.
+
+The tree may be put together by hand, or loaded from a directory on disk
+skipping entries matched by ignore patterns.
+Large trees are searched on a pool of threads, which stream matches back.
+
+Anything else over the whole tree, like sizes or printing it, is done by visitors
+that File and Folder accept, so they don't need a method for each.
+
+Components are found by paths like `a/b/c.txt`, and can be removed, renamed or
+moved to other folders.
 */
 
 mod fs {
     // mod.rs
     pub use file::File;
-    pub use folder::Folder;
+    pub use folder::{Folder, TreeError};
+    pub use ignore::Ignore;
+    pub use query::Query;
+    pub use search::{Cancel, Search};
+    pub use stats::Stats;
+    pub use tree::Tree;
+    pub use visitor::Visitor;
+
+    use std::{fmt, path::PathBuf};
+
+    pub trait Component: Send + Sync {
+        fn name(&self) -> &str;
+
+        fn search(&self, query: &Query) -> Vec<Match>;
 
-    pub trait Component {
-        fn search(&self, keyword: &str);
+        fn accept(&self, visitor: &mut dyn Visitor);
+
+        fn set_name(&mut self, name: String);
+
+        /// Components inside, there are none unless it's a folder.
+        fn components(&self) -> &[Box<dyn Component>] {
+            &[]
+        }
+
+        fn as_folder_mut(&mut self) -> Option<&mut Folder> {
+            None
+        }
+    }
+
+    /// An occurrence of a query. The path starts from the component
+    /// being searched, lines and columns are counted from 1.
+    #[derive(Debug, PartialEq)]
+    pub struct Match {
+        pub path: PathBuf,
+        pub line: usize,
+        pub column: usize,
+        pub text: String,
+    }
+
+    impl fmt::Display for Match {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(
+                f,
+                "{}:{}:{}: {}",
+                self.path.display(),
+                self.line,
+                self.column,
+                self.text
+            )
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::{File, Folder};
+
+        /// site/{index.html, css/{main.css, vendor/{reset.css}}, empty/,
+        /// readme}, 34 bytes in all.
+        pub fn site() -> Folder {
+            let mut vendor = Folder::new("vendor");
+            vendor
+                .add(File::new("reset.css", "* { margin: 0; }\n\n\n\n"))
+                .unwrap();
+            let mut css = Folder::new("css");
+            css.add(File::new("main.css", "body")).unwrap();
+            css.add(vendor).unwrap();
+
+            let mut site = Folder::new("site");
+            site.add(File::new("index.html", "<p>hi</p>\n")).unwrap();
+            site.add(css).unwrap();
+            site.add(Folder::new("empty")).unwrap();
+            site.add(File::new("readme", "")).unwrap();
+            site
+        }
     }
 
     mod file {
-        use super::Component;
+        use std::{io, path::Path};
+
+        use super::{Component, Match, Query, Visitor};
 
         pub struct File {
-            name: &'static str,
+            name: String,
+            content: String,
         }
 
         impl File {
-            pub fn new(name: &'static str) -> Self {
-                Self { name }
+            pub fn new(name: impl Into<String>, content: impl Into<String>) -> Self {
+                Self {
+                    name: name.into(),
+                    content: content.into(),
+                }
+            }
+
+            /// Reads a text file. Files that aren't valid UTF-8 can't be
+            /// searched for text and are reported as `InvalidData`.
+            pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+                let path = path.as_ref();
+                let content = std::fs::read_to_string(path)?;
+                let name = path.file_name().unwrap_or_default().to_string_lossy();
+                Ok(Self::new(name, content))
+            }
+
+            /// Size of the content in bytes.
+            pub fn size(&self) -> usize {
+                self.content.len()
             }
         }
 
         impl Component for File {
-            fn search(&self, keyword: &str) {
-                println!("Searching for keyword {} in file {}", keyword, self.name);
+            fn name(&self) -> &str {
+                &self.name
+            }
+
+            fn search(&self, query: &Query) -> Vec<Match> {
+                if !query.accepts(&self.name) {
+                    return vec![];
+                }
+
+                let mut matches = vec![];
+                for (index, line) in self.content.lines().enumerate() {
+                    for offset in query.find(line) {
+                        matches.push(Match {
+                            path: self.name.as_str().into(),
+                            line: index + 1,
+                            column: line[..offset].chars().count() + 1,
+                            text: line.to_string(),
+                        });
+                    }
+                }
+                matches
+            }
+
+            fn accept(&self, visitor: &mut dyn Visitor) {
+                visitor.visit_file(self);
+            }
+
+            fn set_name(&mut self, name: String) {
+                self.name = name;
+            }
+        }
+
+        #[cfg(test)]
+        mod tests {
+            use super::File;
+            use crate::fs::{Component, Query};
+
+            fn positions(file: &File, query: &Query) -> Vec<(usize, usize, String)> {
+                file.search(query)
+                    .into_iter()
+                    .map(|found| (found.line, found.column, found.text))
+                    .collect()
+            }
+
+            #[test]
+            fn columns_count_characters_rather_than_bytes() {
+                let lines = "héllo wörld\nnö wörld wörld\n🌹 wörld";
+                let file = File::new("wörter.txt", lines);
+
+                assert_eq!(
+                    positions(&file, &Query::literal("wörld")),
+                    [
+                        (1, 7, "héllo wörld".to_string()),
+                        (2, 4, "nö wörld wörld".to_string()),
+                        (2, 10, "nö wörld wörld".to_string()),
+                        (3, 3, "🌹 wörld".to_string()),
+                    ]
+                );
+            }
+
+            #[test]
+            fn lines_are_counted_from_one_whatever_the_line_ends() {
+                let file = File::new("crlf.txt", "rose\r\n\r\na rose\r\n");
+
+                assert_eq!(
+                    positions(&file, &Query::literal("rose")),
+                    [(1, 1, "rose".to_string()), (3, 3, "a rose".to_string())]
+                );
+                let found = &file.search(&Query::literal("rose"))[1];
+                assert_eq!(found.to_string(), "crlf.txt:3:3: a rose");
+            }
+
+            #[test]
+            fn files_the_query_does_not_accept_are_not_searched() {
+                let file = File::new("notes.txt", "rose");
+
+                assert!(file
+                    .search(&Query::literal("rose").extension("rs"))
+                    .is_empty());
+                assert!(file.search(&Query::literal("tulip")).is_empty());
             }
         }
     }
 
     mod folder {
-        use super::Component;
+        use std::{fmt, io, path::Path};
+
+        use super::{Component, File, Ignore, Match, Query, Visitor};
 
         pub struct Folder {
-            name: &'static str,
+            name: String,
             components: Vec<Box<dyn Component>>,
         }
 
         impl Folder {
-            pub fn new(name: &'static str) -> Self {
+            pub fn new(name: impl Into<String>) -> Self {
                 Self {
-                    name,
+                    name: name.into(),
                     components: vec![],
                 }
             }
 
-            pub fn add(&mut self, component: impl Component + 'static) {
+            /// Adds a component, unless its name is invalid or another one
+            /// already has it.
+            pub fn add(&mut self, component: impl Component + 'static) -> Result<(), TreeError> {
+                let name = component.name();
+                check_name(name)?;
+                if self.position(name).is_some() {
+                    return Err(TreeError::Exists(name.into()));
+                }
+
                 self.components.push(Box::new(component));
+                Ok(())
+            }
+
+            /// Loads a directory with everything inside, in name order.
+            /// Ignored entries, symbolic links, names and files that aren't
+            /// text, and whatever can't be read are left out, so only the
+            /// directory itself has to be readable.
+            pub fn open(path: impl AsRef<Path>, ignore: &Ignore) -> io::Result<Self> {
+                let path = path.as_ref();
+                let name = path.file_name().unwrap_or_default().to_string_lossy();
+                let mut folder = Self::new(name);
+
+                let mut entries = std::fs::read_dir(path)?
+                    .filter_map(Result::ok)
+                    .collect::<Vec<_>>();
+                entries.sort_by_key(|entry| entry.file_name());
+
+                for entry in entries {
+                    let Ok(name) = entry.file_name().into_string() else {
+                        continue;
+                    };
+                    if ignore.is_ignored(&name) {
+                        continue;
+                    }
+
+                    // Entries have distinct names, so they are always added.
+                    let invalid = |err| io::Error::new(io::ErrorKind::InvalidData, err);
+                    let Ok(kind) = entry.file_type() else {
+                        continue;
+                    };
+                    if kind.is_dir() {
+                        if let Ok(inner) = Folder::open(entry.path(), ignore) {
+                            folder.add(inner).map_err(invalid)?;
+                        }
+                    } else if kind.is_file() {
+                        if let Ok(file) = File::open(entry.path()) {
+                            folder.add(file).map_err(invalid)?;
+                        }
+                    }
+                }
+
+                Ok(folder)
+            }
+
+            /// Finds a component by its path from this folder, like
+            /// `a/b/c.txt`. An empty path is the folder itself.
+            pub fn find(&self, path: &str) -> Option<&dyn Component> {
+                let mut component: &dyn Component = self;
+                for name in segments(path) {
+                    let mut components = component.components().iter();
+                    component = components.find(|c| c.name() == name)?.as_ref();
+                }
+                Some(component)
+            }
+
+            /// Takes a component out of the tree.
+            pub fn remove(&mut self, path: &str) -> Result<Box<dyn Component>, TreeError> {
+                let (parent, name) = self.parent_mut(path)?;
+                let index = parent
+                    .position(name)
+                    .ok_or_else(|| TreeError::NotFound(path.into()))?;
+                Ok(parent.components.remove(index))
+            }
+
+            /// Renames a component, unless another one in its folder already
+            /// has the name.
+            pub fn rename(&mut self, path: &str, name: impl Into<String>) -> Result<(), TreeError> {
+                let name = name.into();
+                check_name(&name)?;
+
+                let (parent, old) = self.parent_mut(path)?;
+                let index = parent
+                    .position(old)
+                    .ok_or_else(|| TreeError::NotFound(path.into()))?;
+                if name != old && parent.position(&name).is_some() {
+                    return Err(TreeError::Exists(sibling(path, &name)));
+                }
+
+                parent.components[index].set_name(name);
+                Ok(())
+            }
+
+            /// Moves a component into another folder, keeping its name. The
+            /// tree is left as it was if the move isn't possible.
+            pub fn move_to(&mut self, from: &str, to: &str) -> Result<(), TreeError> {
+                let source = segments(from).collect::<Vec<_>>();
+                let Some(name) = self.find(from).filter(|_| !source.is_empty()) else {
+                    return Err(TreeError::NotFound(from.into()));
+                };
+                let name = name.name().to_string();
+
+                if segments(to).take(source.len()).eq(source.iter().copied()) {
+                    return Err(TreeError::IntoItself(from.into()));
+                }
+
+                let target = self.folder_mut(to)?;
+                if target.position(&name).is_some() {
+                    let path = segments(to).chain([name.as_str()]).collect::<Vec<_>>();
+                    return Err(TreeError::Exists(path.join("/")));
+                }
+
+                let component = self.remove(from)?;
+                self.folder_mut(to)?.components.push(component);
+                Ok(())
+            }
+
+            fn position(&self, name: &str) -> Option<usize> {
+                self.components.iter().position(|c| c.name() == name)
+            }
+
+            fn folder_mut(&mut self, path: &str) -> Result<&mut Folder, TreeError> {
+                let mut folder = self;
+                let mut walked = vec![];
+                for name in segments(path) {
+                    walked.push(name);
+                    let index = folder
+                        .position(name)
+                        .ok_or_else(|| TreeError::NotFound(walked.join("/")))?;
+                    folder = folder.components[index]
+                        .as_folder_mut()
+                        .ok_or_else(|| TreeError::NotAFolder(walked.join("/")))?;
+                }
+                Ok(folder)
+            }
+
+            /// Finds the folder holding the component at the path, and the
+            /// name of the component.
+            fn parent_mut<'p>(
+                &mut self,
+                path: &'p str,
+            ) -> Result<(&mut Folder, &'p str), TreeError> {
+                let mut names = segments(path).collect::<Vec<_>>();
+                let name = names
+                    .pop()
+                    .ok_or_else(|| TreeError::NotFound(path.into()))?;
+                Ok((self.folder_mut(&names.join("/"))?, name))
+            }
+        }
+
+        /// A name can't be empty, or hold a path.
+        fn check_name(name: &str) -> Result<(), TreeError> {
+            if name.is_empty() || name.contains('/') {
+                return Err(TreeError::InvalidName(name.into()));
+            }
+            Ok(())
+        }
+
+        /// Names on the path, repeated and trailing slashes don't count.
+        fn segments(path: &str) -> impl Iterator<Item = &str> {
+            path.split('/').filter(|name| !name.is_empty())
+        }
+
+        /// The path of a component with another name in the same folder.
+        fn sibling(path: &str, name: &str) -> String {
+            let mut names = segments(path).collect::<Vec<_>>();
+            names.pop();
+            names.push(name);
+            names.join("/")
+        }
+
+        #[derive(Debug)]
+        pub enum TreeError {
+            NotFound(String),
+            NotAFolder(String),
+            Exists(String),
+            InvalidName(String),
+            IntoItself(String),
+        }
+
+        impl fmt::Display for TreeError {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                match self {
+                    TreeError::NotFound(path) => write!(f, "no such path `{}`", path),
+                    TreeError::NotAFolder(path) => write!(f, "`{}` is not a folder", path),
+                    TreeError::Exists(path) => write!(f, "`{}` already exists", path),
+                    TreeError::InvalidName(name) => write!(f, "invalid name `{}`", name),
+                    TreeError::IntoItself(path) => {
+                        write!(f, "cannot move `{}` into itself", path)
+                    }
+                }
             }
         }
 
+        impl std::error::Error for TreeError {}
+
         impl Component for Folder {
-            fn search(&self, keyword: &str) {
-                println!(
-                    "Searching recursively for keyword {} in folder {}",
-                    keyword, self.name
-                );
+            fn name(&self) -> &str {
+                &self.name
+            }
+
+            fn search(&self, query: &Query) -> Vec<Match> {
+                let mut matches = vec![];
+                for component in self.components.iter() {
+                    for mut found in component.search(query) {
+                        found.path = Path::new(&self.name).join(found.path);
+                        matches.push(found);
+                    }
+                }
+                matches
+            }
 
+            fn accept(&self, visitor: &mut dyn Visitor) {
+                visitor.enter_folder(self);
                 for component in self.components.iter() {
-                    component.search(keyword);
+                    component.accept(visitor);
+                }
+                visitor.leave_folder(self);
+            }
+
+            fn set_name(&mut self, name: String) {
+                self.name = name;
+            }
+
+            fn components(&self) -> &[Box<dyn Component>] {
+                &self.components
+            }
+
+            fn as_folder_mut(&mut self) -> Option<&mut Folder> {
+                Some(self)
+            }
+        }
+
+        #[cfg(test)]
+        mod tests {
+            use std::{
+                ffi::OsStr,
+                fs::{self, Permissions},
+                io,
+                os::unix::{ffi::OsStrExt, fs::PermissionsExt},
+            };
+
+            use super::{Folder, TreeError};
+            use crate::fs::{Component, File, Ignore, Tree};
+
+            /// docs/{a.txt, drafts/{b.txt}} and notes.txt.
+            fn root() -> Folder {
+                let mut drafts = Folder::new("drafts");
+                drafts.add(File::new("b.txt", "bb")).unwrap();
+                let mut docs = Folder::new("docs");
+                docs.add(File::new("a.txt", "a")).unwrap();
+                docs.add(drafts).unwrap();
+
+                let mut root = Folder::new("root");
+                root.add(docs).unwrap();
+                root.add(File::new("notes.txt", "n")).unwrap();
+                root
+            }
+
+            fn drawn(root: &Folder) -> String {
+                Tree::of(root).to_string()
+            }
+
+            /// What went wrong, as told to the user.
+            fn error<T>(result: Result<T, TreeError>) -> String {
+                match result {
+                    Ok(_) => panic!("the tree was edited"),
+                    Err(err) => err.to_string(),
+                }
+            }
+
+            #[test]
+            fn add_refuses_taken_and_invalid_names() {
+                let mut root = root();
+                let before = drawn(&root);
+
+                let errors = [
+                    (
+                        File::new("notes.txt", "again"),
+                        "`notes.txt` already exists",
+                    ),
+                    (File::new("", ""), "invalid name ``"),
+                    (File::new("a/b", ""), "invalid name `a/b`"),
+                ];
+                for (file, message) in errors {
+                    assert_eq!(error(root.add(file)), message);
+                }
+                assert_eq!(
+                    error(root.add(Folder::new("docs"))),
+                    "`docs` already exists"
+                );
+                assert_eq!(drawn(&root), before);
+            }
+
+            #[test]
+            fn components_are_found_by_path() {
+                let root = root();
+
+                assert_eq!(root.find("").map(|c| c.name()), Some("root"));
+                assert_eq!(
+                    root.find("docs/drafts/b.txt").map(|c| c.name()),
+                    Some("b.txt")
+                );
+                assert_eq!(
+                    root.find("/docs//drafts/").map(|c| c.name()),
+                    Some("drafts")
+                );
+                assert!(root.find("docs/c.txt").is_none());
+                assert!(root.find("notes.txt/a.txt").is_none());
+            }
+
+            #[test]
+            fn remove_takes_a_component_out() {
+                let mut root = root();
+
+                let removed = root.remove("docs/drafts").unwrap();
+                assert_eq!(removed.name(), "drafts");
+                assert_eq!(removed.components().len(), 1);
+                assert!(root.find("docs/drafts").is_none());
+
+                let errors = [
+                    ("docs/drafts", "no such path `docs/drafts`"),
+                    ("", "no such path ``"),
+                    ("notes.txt/x", "`notes.txt` is not a folder"),
+                    ("missing/x", "no such path `missing`"),
+                ];
+                for (path, message) in errors {
+                    assert_eq!(error(root.remove(path)), message);
+                }
+            }
+
+            #[test]
+            fn rename_keeps_names_unique_and_valid() {
+                let mut root = root();
+
+                root.rename("docs/a.txt", "c.txt").unwrap();
+                root.rename("docs/c.txt", "c.txt").unwrap();
+                assert!(root.find("docs/c.txt").is_some());
+                let before = drawn(&root);
+
+                let errors = [
+                    ("docs/c.txt", "drafts", "`docs/drafts` already exists"),
+                    ("docs/c.txt", "x/y", "invalid name `x/y`"),
+                    ("docs/c.txt", "", "invalid name ``"),
+                    ("docs/a.txt", "d.txt", "no such path `docs/a.txt`"),
+                ];
+                for (path, name, message) in errors {
+                    assert_eq!(error(root.rename(path, name)), message);
+                }
+                assert_eq!(drawn(&root), before);
+            }
+
+            #[test]
+            fn move_to_keeps_the_name_in_the_new_folder() {
+                let mut root = root();
+
+                root.move_to("notes.txt", "docs/drafts").unwrap();
+                assert!(root.find("docs/drafts/notes.txt").is_some());
+                assert!(root.find("notes.txt").is_none());
+
+                root.move_to("docs/drafts", "").unwrap();
+                assert!(root.find("drafts/notes.txt").is_some());
+            }
+
+            #[test]
+            fn impossible_moves_leave_the_tree_as_it_was() {
+                let mut root = root();
+                root.add(File::new("a.txt", "other")).unwrap();
+                let before = drawn(&root);
+
+                let errors = [
+                    ("docs", "docs", "cannot move `docs` into itself"),
+                    ("docs", "docs/drafts", "cannot move `docs` into itself"),
+                    ("a.txt", "docs", "`docs/a.txt` already exists"),
+                    ("docs/a.txt", "", "`a.txt` already exists"),
+                    ("docs", "notes.txt", "`notes.txt` is not a folder"),
+                    ("docs/x.txt", "", "no such path `docs/x.txt`"),
+                    ("notes.txt", "docs/x", "no such path `docs/x`"),
+                    ("", "docs", "no such path ``"),
+                ];
+                for (from, to, message) in errors {
+                    assert_eq!(error(root.move_to(from, to)), message, "{} to {}", from, to);
+                }
+                assert_eq!(drawn(&root), before);
+            }
+
+            #[test]
+            fn directory_is_loaded_without_ignored_entries() {
+                let dir = tempfile::tempdir().unwrap();
+                let root = dir.path();
+                fs::create_dir_all(root.join("src/bin")).unwrap();
+                fs::create_dir_all(root.join("target/debug")).unwrap();
+                fs::write(root.join("src/main.rs"), "fn main() {}").unwrap();
+                fs::write(root.join("src/bin/tool.rs"), "fn tool() {}").unwrap();
+                fs::write(root.join("target/debug/out.rs"), "fn out() {}").unwrap();
+                fs::write(root.join(".env"), "KEY=1").unwrap();
+                fs::write(root.join("Cargo.lock"), "lock").unwrap();
+                fs::write(root.join("README"), "readme").unwrap();
+
+                let folder = Folder::open(root, &Ignore::new(["target", ".*", "*.lock"])).unwrap();
+
+                let expected = format!(
+                    "{}\n{}",
+                    root.file_name().unwrap().to_str().unwrap(),
+                    "\
+├── README (6 B)
+└── src
+    ├── bin
+    │   └── tool.rs (12 B)
+    └── main.rs (12 B)
+"
+                );
+                assert_eq!(drawn(&folder), expected);
+            }
+
+            #[test]
+            fn entries_that_cannot_be_read_as_text_are_left_out() {
+                let dir = tempfile::tempdir().unwrap();
+                let root = dir.path();
+                fs::write(root.join("a.txt"), "rose").unwrap();
+                fs::write(root.join("binary.txt"), [0xff, 0xfe, 0x00]).unwrap();
+                fs::write(root.join(OsStr::from_bytes(b"\xffname.txt")), "rose").unwrap();
+                fs::create_dir(root.join("locked")).unwrap();
+                fs::write(root.join("locked/b.txt"), "rose").unwrap();
+                fs::write(root.join("secret.txt"), "rose").unwrap();
+                fs::create_dir(root.join("sub")).unwrap();
+                fs::write(root.join("sub/c.txt"), "rose").unwrap();
+                for locked in ["locked", "secret.txt"] {
+                    fs::set_permissions(root.join(locked), Permissions::from_mode(0o000)).unwrap();
+                }
+
+                let folder = Folder::open(root, &Ignore::default()).unwrap();
+
+                // Some users, like root, can read anything.
+                let mut expected = vec!["a.txt"];
+                if fs::read(root.join("locked/b.txt")).is_ok() {
+                    expected.push("locked");
+                }
+                if fs::read(root.join("secret.txt")).is_ok() {
+                    expected.push("secret.txt");
+                }
+                expected.push("sub");
+                let names: Vec<&str> = folder.components().iter().map(|c| c.name()).collect();
+                assert_eq!(names, expected);
+                assert!(folder.find("sub/c.txt").is_some());
+
+                // So the directory can be cleaned up.
+                fs::set_permissions(root.join("locked"), Permissions::from_mode(0o700)).unwrap();
+            }
+
+            #[test]
+            fn unreadable_directory_is_an_error() {
+                let dir = tempfile::tempdir().unwrap();
+
+                let missing = Folder::open(dir.path().join("missing"), &Ignore::default());
+
+                assert_eq!(missing.err().unwrap().kind(), io::ErrorKind::NotFound);
+            }
+
+            #[test]
+            fn errors_name_the_path() {
+                let errors = [
+                    (TreeError::NotFound("a/b".into()), "no such path `a/b`"),
+                    (TreeError::NotAFolder("a".into()), "`a` is not a folder"),
+                    (TreeError::Exists("a".into()), "`a` already exists"),
+                    (TreeError::InvalidName("a/b".into()), "invalid name `a/b`"),
+                    (
+                        TreeError::IntoItself("a".into()),
+                        "cannot move `a` into itself",
+                    ),
+                ];
+                for (error, message) in errors {
+                    assert_eq!(error.to_string(), message);
+                }
+            }
+        }
+    }
+
+    mod visitor {
+        use super::{File, Folder};
+
+        /// Walks a tree with `Component::accept`: a folder is entered, its
+        /// components are visited in order, then it is left.
+        pub trait Visitor {
+            fn visit_file(&mut self, file: &File);
+
+            fn enter_folder(&mut self, _folder: &Folder) {}
+
+            fn leave_folder(&mut self, _folder: &Folder) {}
+        }
+    }
+
+    mod stats {
+        use std::path::PathBuf;
+
+        use super::{Component, File, Folder, Visitor};
+
+        /// Totals over a tree. The depth is how many levels of folders are
+        /// above the deepest component, so a lone file has none.
+        #[derive(Debug, Default)]
+        pub struct Stats {
+            pub size: usize,
+            pub files: usize,
+            pub folders: usize,
+            pub depth: usize,
+            /// Paths and sizes of the largest files, the largest first.
+            pub largest: Vec<(PathBuf, usize)>,
+            keep: usize,
+            path: PathBuf,
+            level: usize,
+        }
+
+        impl Stats {
+            /// Gathers stats of a tree, keeping up to `largest` of its
+            /// largest files.
+            pub fn of(component: &dyn Component, largest: usize) -> Self {
+                let mut stats = Self {
+                    keep: largest,
+                    ..Self::default()
+                };
+                component.accept(&mut stats);
+                stats
+            }
+        }
+
+        impl Visitor for Stats {
+            fn visit_file(&mut self, file: &File) {
+                self.depth = self.depth.max(self.level);
+                self.files += 1;
+                self.size += file.size();
+
+                // Files of the same size stay in the order they were found.
+                let size = file.size();
+                let index = self.largest.partition_point(|(_, other)| *other >= size);
+                if index < self.keep {
+                    self.largest
+                        .insert(index, (self.path.join(file.name()), size));
+                    self.largest.truncate(self.keep);
+                }
+            }
+
+            fn enter_folder(&mut self, folder: &Folder) {
+                if self.level > 0 {
+                    self.folders += 1;
+                    self.depth = self.depth.max(self.level);
+                }
+                self.path.push(folder.name());
+                self.level += 1;
+            }
+
+            fn leave_folder(&mut self, _folder: &Folder) {
+                self.path.pop();
+                self.level -= 1;
+            }
+        }
+
+        #[cfg(test)]
+        mod tests {
+            use std::path::PathBuf;
+
+            use super::Stats;
+            use crate::fs::{tests::site, File, Folder};
+
+            fn largest(stats: &Stats) -> Vec<(PathBuf, usize)> {
+                stats.largest.clone()
+            }
+
+            #[test]
+            fn totals_cover_the_whole_tree() {
+                let stats = Stats::of(&site(), 2);
+
+                assert_eq!(stats.size, 34);
+                assert_eq!(stats.files, 4);
+                assert_eq!(stats.folders, 3);
+                assert_eq!(stats.depth, 3);
+                assert_eq!(
+                    largest(&stats),
+                    [
+                        ("site/css/vendor/reset.css".into(), 20),
+                        ("site/index.html".into(), 10)
+                    ]
+                );
+            }
+
+            #[test]
+            fn largest_files_are_kept_up_to_the_count_asked_for() {
+                assert!(Stats::of(&site(), 0).largest.is_empty());
+
+                let sizes: Vec<usize> = Stats::of(&site(), 10)
+                    .largest
+                    .iter()
+                    .map(|(_, size)| *size)
+                    .collect();
+                assert_eq!(sizes, [20, 10, 4, 0]);
+            }
+
+            #[test]
+            fn files_of_the_same_size_stay_in_the_order_found() {
+                let mut folder = Folder::new("f");
+                for name in ["b", "a", "c"] {
+                    folder.add(File::new(name, "same")).unwrap();
+                }
+
+                let stats = Stats::of(&folder, 2);
+                assert_eq!(largest(&stats), [("f/b".into(), 4), ("f/a".into(), 4)]);
+            }
+
+            #[test]
+            fn empty_folders_count_but_hold_nothing() {
+                let stats = Stats::of(&Folder::new("empty"), 3);
+                assert_eq!(
+                    (stats.size, stats.files, stats.folders, stats.depth),
+                    (0, 0, 0, 0)
+                );
+                assert!(stats.largest.is_empty());
+
+                let mut outer = Folder::new("outer");
+                outer.add(Folder::new("empty")).unwrap();
+                let stats = Stats::of(&outer, 3);
+                assert_eq!((stats.files, stats.folders, stats.depth), (0, 1, 1));
+            }
+
+            #[test]
+            fn lone_file_has_no_depth() {
+                let stats = Stats::of(&File::new("a", "x"), 3);
+
+                assert_eq!((stats.size, stats.files, stats.depth), (1, 1, 0));
+                assert_eq!(largest(&stats), [("a".into(), 1)]);
+            }
+        }
+    }
+
+    mod tree {
+        use std::fmt;
+
+        use super::{Component, File, Folder, Visitor};
+
+        /// Draws a tree like the `tree` command does, with file sizes.
+        #[derive(Default)]
+        pub struct Tree {
+            lines: Vec<String>,
+            /// How many components are left to draw in each open folder.
+            left: Vec<usize>,
+        }
+
+        impl Tree {
+            pub fn of(component: &dyn Component) -> Self {
+                let mut tree = Self::default();
+                component.accept(&mut tree);
+                tree
+            }
+
+            fn draw(&mut self, name: String) {
+                let Some((parent, ancestors)) = self.left.split_last_mut() else {
+                    self.lines.push(name);
+                    return;
+                };
+
+                *parent -= 1;
+                let branch = if *parent == 0 {
+                    "└── "
+                } else {
+                    "├── "
+                };
+                let mut line = String::new();
+                for left in ancestors.iter() {
+                    line.push_str(if *left == 0 { "    " } else { "│   " });
+                }
+                line.push_str(branch);
+                line.push_str(&name);
+                self.lines.push(line);
+            }
+        }
+
+        impl Visitor for Tree {
+            fn visit_file(&mut self, file: &File) {
+                self.draw(format!("{} ({} B)", file.name(), file.size()));
+            }
+
+            fn enter_folder(&mut self, folder: &Folder) {
+                self.draw(folder.name().to_string());
+                self.left.push(folder.components().len());
+            }
+
+            fn leave_folder(&mut self, _folder: &Folder) {
+                self.left.pop();
+            }
+        }
+
+        impl fmt::Display for Tree {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                for line in self.lines.iter() {
+                    writeln!(f, "{}", line)?;
+                }
+                Ok(())
+            }
+        }
+
+        #[cfg(test)]
+        mod tests {
+            use super::Tree;
+            use crate::fs::{tests::site, File, Folder};
+
+            #[test]
+            fn tree_is_drawn_with_file_sizes() {
+                let expected = "\
+site
+├── index.html (10 B)
+├── css
+│   ├── main.css (4 B)
+│   └── vendor
+│       └── reset.css (20 B)
+├── empty
+└── readme (0 B)
+";
+                assert_eq!(Tree::of(&site()).to_string(), expected);
+            }
+
+            #[test]
+            fn empty_folder_and_lone_file_are_one_line() {
+                assert_eq!(Tree::of(&Folder::new("empty")).to_string(), "empty\n");
+                assert_eq!(Tree::of(&File::new("a", "x")).to_string(), "a (1 B)\n");
+            }
+        }
+    }
+
+    mod search {
+        use std::{
+            collections::VecDeque,
+            path::PathBuf,
+            sync::{
+                atomic::{AtomicBool, AtomicUsize, Ordering},
+                mpsc::Sender,
+                Arc, Condvar, Mutex,
+            },
+            thread,
+        };
+
+        use super::{Component, Match, Query};
+
+        /// Stops a search from another thread. Components already being
+        /// searched are finished, nothing new is started.
+        #[derive(Clone, Default)]
+        pub struct Cancel(Arc<AtomicBool>);
+
+        impl Cancel {
+            pub fn cancel(&self) {
+                self.0.store(true, Ordering::Relaxed);
+            }
+
+            pub fn is_cancelled(&self) -> bool {
+                self.0.load(Ordering::Relaxed)
+            }
+        }
+
+        /// Searches a tree on a pool of threads, each taking the next
+        /// component from a shared queue: a folder puts its components
+        /// into the queue, anything else is searched right away.
+        pub struct Search {
+            query: Query,
+            threads: usize,
+            limit: usize,
+            cancel: Cancel,
+        }
+
+        /// Where a match is in the tree: indices of the components on
+        /// the way to it, then its number among the component's matches.
+        /// Sorting by it gives the order of a sequential search.
+        type Position = (Vec<usize>, usize);
+
+        struct Task<'a> {
+            component: &'a dyn Component,
+            path: PathBuf,
+            position: Vec<usize>,
+        }
+
+        struct Queue<'a> {
+            tasks: VecDeque<Task<'a>>,
+            busy: usize,
+        }
+
+        impl Search {
+            pub fn new(query: Query) -> Self {
+                Self {
+                    query,
+                    threads: thread::available_parallelism().map_or(1, |count| count.get()),
+                    limit: usize::MAX,
+                    cancel: Cancel::default(),
+                }
+            }
+
+            pub fn threads(mut self, threads: usize) -> Self {
+                self.threads = threads.max(1);
+                self
+            }
+
+            /// Stops after this many matches. Which ones are found first
+            /// depends on the threads, so they may differ between runs.
+            pub fn limit(mut self, limit: usize) -> Self {
+                self.limit = limit;
+                self
+            }
+
+            pub fn canceller(&self) -> Cancel {
+                self.cancel.clone()
+            }
+
+            /// Sends matches as soon as they are found, in no particular
+            /// order. Returns when the search is over, or nobody receives
+            /// matches anymore.
+            pub fn stream(&self, root: &dyn Component, matches: Sender<Match>) {
+                self.run(root, &|_, found| matches.send(found).is_ok());
+            }
+
+            /// Collects matches in the same order as `Component::search`.
+            pub fn collect(&self, root: &dyn Component) -> Vec<Match> {
+                let found = Mutex::new(vec![]);
+                self.run(root, &|position, matched| {
+                    found.lock().unwrap().push((position, matched));
+                    true
+                });
+
+                let mut found = found.into_inner().unwrap();
+                found.sort_by(|(a, _), (b, _)| a.cmp(b));
+                found.into_iter().map(|(_, matched)| matched).collect()
+            }
+
+            fn run(&self, root: &dyn Component, send: &(dyn Fn(Position, Match) -> bool + Sync)) {
+                let root = Task {
+                    component: root,
+                    path: PathBuf::new(),
+                    position: vec![],
+                };
+                let queue = Mutex::new(Queue {
+                    tasks: VecDeque::from([root]),
+                    busy: 0,
+                });
+                let ready = Condvar::new();
+                let sent = AtomicUsize::new(0);
+                // Set when the search is over early; unlike the canceller,
+                // it doesn't outlive this run.
+                let stop = AtomicBool::new(false);
+
+                let stopped = || stop.load(Ordering::Relaxed) || self.cancel.is_cancelled();
+
+                let work = || loop {
+                    let task = {
+                        let mut queue = queue.lock().unwrap();
+                        loop {
+                            if stopped() {
+                                return;
+                            }
+                            if let Some(task) = queue.tasks.pop_front() {
+                                queue.busy += 1;
+                                break task;
+                            }
+                            if queue.busy == 0 {
+                                return;
+                            }
+                            queue = ready.wait(queue).unwrap();
+                        }
+                    };
+
+                    let components = task.component.components();
+                    if components.is_empty() {
+                        let found = task.component.search(&self.query);
+                        for (index, mut matched) in found.into_iter().enumerate() {
+                            if sent.fetch_add(1, Ordering::Relaxed) >= self.limit {
+                                stop.store(true, Ordering::Relaxed);
+                                break;
+                            }
+                            matched.path = task.path.join(matched.path);
+                            if !send((task.position.clone(), index), matched) {
+                                stop.store(true, Ordering::Relaxed);
+                                break;
+                            }
+                        }
+                    }
+
+                    let mut queue = queue.lock().unwrap();
+                    let path = task.path.join(task.component.name());
+                    for (index, component) in components.iter().enumerate() {
+                        let mut position = task.position.clone();
+                        position.push(index);
+                        queue.tasks.push_back(Task {
+                            component: component.as_ref(),
+                            path: path.clone(),
+                            position,
+                        });
+                    }
+                    queue.busy -= 1;
+                    ready.notify_all();
+                };
+
+                thread::scope(|scope| {
+                    for _ in 0..self.threads {
+                        scope.spawn(work);
+                    }
+                });
+            }
+        }
+
+        #[cfg(test)]
+        mod tests {
+            use std::sync::mpsc;
+
+            use super::Search;
+            use crate::fs::{Component, File, Folder, Query};
+
+            const THREADS: [usize; 3] = [1, 2, 8];
+
+            /// Folders three levels deep with several roses in every file.
+            fn garden() -> Folder {
+                let mut garden = Folder::new("Garden");
+                for bed in 0..4 {
+                    let mut folder = Folder::new(format!("Bed {}", bed));
+                    for row in 0..3 {
+                        let mut inner = Folder::new(format!("Row {}", row));
+                        for plant in 0..3 {
+                            inner
+                                .add(File::new(
+                                    format!("Plant {}", plant),
+                                    "a rose is a rose\nis a rose\nthorns",
+                                ))
+                                .unwrap();
+                        }
+                        folder.add(inner).unwrap();
+                    }
+                    folder
+                        .add(File::new("Notes", "no rose here? one rose"))
+                        .unwrap();
+                    garden.add(folder).unwrap();
+                }
+                garden.add(File::new("Gate", "closed")).unwrap();
+                garden
+            }
+
+            fn query() -> Query {
+                Query::literal("rose")
+            }
+
+            #[test]
+            fn collect_is_the_same_as_sequential_search() {
+                let garden = garden();
+                let sequential = garden.search(&query());
+                assert_eq!(sequential.len(), 4 * (3 * 3 * 3 + 2));
+
+                for threads in THREADS {
+                    let parallel = Search::new(query()).threads(threads).collect(&garden);
+                    assert_eq!(parallel, sequential, "{} threads", threads);
                 }
             }
+
+            #[test]
+            fn limit_yields_exactly_that_many_matches() {
+                let garden = garden();
+
+                for threads in THREADS {
+                    for limit in [0, 1, 5, 50] {
+                        let search = Search::new(query()).threads(threads).limit(limit);
+                        assert_eq!(search.collect(&garden).len(), limit, "{} threads", threads);
+
+                        let (sender, receiver) = mpsc::channel();
+                        search.stream(&garden, sender);
+                        assert_eq!(receiver.iter().count(), limit, "{} threads", threads);
+                    }
+                }
+            }
+
+            #[test]
+            fn cancelled_search_finds_nothing() {
+                let garden = garden();
+
+                for threads in THREADS {
+                    let search = Search::new(query()).threads(threads);
+                    search.canceller().cancel();
+                    assert!(search.collect(&garden).is_empty(), "{} threads", threads);
+                }
+            }
+        }
+    }
+
+    mod query {
+        use std::path::Path;
+
+        use regex::{Regex, RegexBuilder};
+
+        use super::ignore;
+
+        /// What to look for, and in which files. Every kind of pattern is
+        /// compiled to a regular expression, so files are searched the same
+        /// way whatever the kind.
+        #[derive(Clone)]
+        pub struct Query {
+            pattern: Regex,
+            names: Vec<String>,
+            extensions: Vec<String>,
+        }
+
+        impl Query {
+            pub fn literal(text: &str) -> Self {
+                Self::compile(&regex::escape(text), false)
+            }
+
+            pub fn ignore_case(text: &str) -> Self {
+                Self::compile(&regex::escape(text), true)
+            }
+
+            /// Matches the text only where it isn't a part of a longer word,
+            /// so `rose` finds "a rose," but not "roses".
+            pub fn whole_word(text: &str) -> Self {
+                let is_word =
+                    |char: Option<char>| char.is_some_and(|c| c.is_alphanumeric() || c == '_');
+                let start = if is_word(text.chars().next()) {
+                    r"\b"
+                } else {
+                    ""
+                };
+                let end = if is_word(text.chars().next_back()) {
+                    r"\b"
+                } else {
+                    ""
+                };
+                Self::compile(&format!("{}{}{}", start, regex::escape(text), end), false)
+            }
+
+            /// Matches the characters of the text in order, with anything in
+            /// between and in any case, so `rbn` finds "rose by any other name".
+            pub fn fuzzy(text: &str) -> Self {
+                let chars = text
+                    .chars()
+                    .map(|char| regex::escape(char.encode_utf8(&mut [0; 4])));
+                Self::compile(&chars.collect::<Vec<_>>().join(".*?"), true)
+            }
+
+            pub fn regex(pattern: &str) -> Result<Self, regex::Error> {
+                Ok(Self::from(Regex::new(pattern)?))
+            }
+
+            /// Only looks into files with names fitting the pattern, like
+            /// `*.rs` or `main.?s`. Any of several patterns will do.
+            pub fn name(mut self, pattern: impl Into<String>) -> Self {
+                self.names.push(pattern.into());
+                self
+            }
+
+            /// Only looks into files with the extension. Any of several
+            /// extensions will do.
+            pub fn extension(mut self, extension: impl Into<String>) -> Self {
+                self.extensions.push(extension.into());
+                self
+            }
+
+            /// Tells whether a file of that name is to be searched.
+            pub fn accepts(&self, name: &str) -> bool {
+                let extension = Path::new(name).extension().and_then(|ext| ext.to_str());
+                let named = self
+                    .names
+                    .iter()
+                    .any(|pattern| ignore::matches(pattern, name));
+                let typed = self
+                    .extensions
+                    .iter()
+                    .any(|ext| Some(ext.as_str()) == extension);
+                (self.names.is_empty() || named) && (self.extensions.is_empty() || typed)
+            }
+
+            /// Byte offsets of the matches in a line. Empty matches, which
+            /// a regular expression may give, are skipped.
+            pub fn find<'a>(&'a self, line: &'a str) -> impl Iterator<Item = usize> + 'a {
+                self.pattern
+                    .find_iter(line)
+                    .filter(|found| !found.is_empty())
+                    .map(|found| found.start())
+            }
+
+            fn compile(pattern: &str, ignore_case: bool) -> Self {
+                // Escaped text always makes a valid expression.
+                let pattern = RegexBuilder::new(pattern)
+                    .case_insensitive(ignore_case)
+                    .build()
+                    .unwrap();
+                Self::from(pattern)
+            }
+        }
+
+        impl From<Regex> for Query {
+            fn from(pattern: Regex) -> Self {
+                Self {
+                    pattern,
+                    names: vec![],
+                    extensions: vec![],
+                }
+            }
+        }
+
+        #[cfg(test)]
+        mod tests {
+            use super::Query;
+            use crate::fs::{Component, File, Folder};
+
+            fn notes() -> Folder {
+                let mut docs = Folder::new("docs");
+                docs.add(File::new("readme.md", "prose about a rose_bed"))
+                    .unwrap();
+
+                let mut notes = Folder::new("notes");
+                notes
+                    .add(File::new(
+                        "poem.txt",
+                        "Roses are red,\na rose by any other name\nrosebud",
+                    ))
+                    .unwrap();
+                notes
+                    .add(File::new("main.rs", "fn rose() {}\nlet ROSE = 1;"))
+                    .unwrap();
+                notes.add(docs).unwrap();
+                notes
+            }
+
+            #[test]
+            fn every_kind_of_query_finds_its_matches() {
+                const POEM: &str = "notes/poem.txt";
+                const MAIN: &str = "notes/main.rs";
+                const README: &str = "notes/docs/readme.md";
+
+                let cases = [
+                    (
+                        "literal",
+                        Query::literal("rose"),
+                        vec![
+                            (POEM, 2, 3),
+                            (POEM, 3, 1),
+                            (MAIN, 1, 4),
+                            (README, 1, 2),
+                            (README, 1, 15),
+                        ],
+                    ),
+                    (
+                        "ignore case",
+                        Query::ignore_case("rose"),
+                        vec![
+                            (POEM, 1, 1),
+                            (POEM, 2, 3),
+                            (POEM, 3, 1),
+                            (MAIN, 1, 4),
+                            (MAIN, 2, 5),
+                            (README, 1, 2),
+                            (README, 1, 15),
+                        ],
+                    ),
+                    (
+                        "whole word",
+                        Query::whole_word("rose"),
+                        vec![(POEM, 2, 3), (MAIN, 1, 4)],
+                    ),
+                    ("fuzzy", Query::fuzzy("RBN"), vec![(POEM, 2, 3)]),
+                    (
+                        "regex",
+                        Query::regex(r"\bro\w*").unwrap(),
+                        vec![(POEM, 2, 3), (POEM, 3, 1), (MAIN, 1, 4), (README, 1, 15)],
+                    ),
+                    (
+                        "extension",
+                        Query::ignore_case("rose").extension("rs"),
+                        vec![(MAIN, 1, 4), (MAIN, 2, 5)],
+                    ),
+                    (
+                        "name",
+                        Query::literal("rose").name("*.txt"),
+                        vec![(POEM, 2, 3), (POEM, 3, 1)],
+                    ),
+                    (
+                        "any of the names",
+                        Query::literal("rose").name("*.md").name("main.?s"),
+                        vec![(MAIN, 1, 4), (README, 1, 2), (README, 1, 15)],
+                    ),
+                    (
+                        "name and extension",
+                        Query::literal("rose").name("*.txt").extension("rs"),
+                        vec![],
+                    ),
+                ];
+
+                let notes = notes();
+                for (kind, query, expected) in cases {
+                    let found: Vec<_> = notes
+                        .search(&query)
+                        .into_iter()
+                        .map(|found| (found.path.display().to_string(), found.line, found.column))
+                        .collect();
+                    let expected: Vec<_> = expected
+                        .into_iter()
+                        .map(|(path, line, column)| (path.to_string(), line, column))
+                        .collect();
+                    assert_eq!(found, expected, "{}", kind);
+                }
+            }
+
+            #[test]
+            fn files_without_an_extension_are_left_out_by_extension() {
+                let query = Query::literal("x").extension("rs");
+
+                assert!(query.accepts("main.rs"));
+                assert!(!query.accepts("Makefile"));
+                assert!(!query.accepts("main.rs.bak"));
+                assert!(Query::literal("x").accepts("Makefile"));
+            }
+
+            #[test]
+            fn invalid_regex_is_an_error() {
+                assert!(Query::regex("(rose").is_err());
+                assert!(Query::regex("[a-").is_err());
+                // Other kinds take any text literally.
+                assert_eq!(
+                    Query::literal("(rose").find("a (rose)").collect::<Vec<_>>(),
+                    [2]
+                );
+            }
+        }
+    }
+
+    mod ignore {
+        /// Name patterns of entries to skip when loading from disk, like
+        /// `target` or `*.lock`: `*` stands for any run of characters and
+        /// `?` for a single one.
+        #[derive(Default)]
+        pub struct Ignore {
+            patterns: Vec<String>,
+        }
+
+        impl Ignore {
+            pub fn new<S: Into<String>>(patterns: impl IntoIterator<Item = S>) -> Self {
+                Self {
+                    patterns: patterns.into_iter().map(Into::into).collect(),
+                }
+            }
+
+            pub fn is_ignored(&self, name: &str) -> bool {
+                self.patterns.iter().any(|pattern| matches(pattern, name))
+            }
+        }
+
+        /// Tells whether a name fits a pattern with `*` and `?` wildcards.
+        pub(super) fn matches(pattern: &str, name: &str) -> bool {
+            let pattern = pattern.chars().collect::<Vec<_>>();
+            let name = name.chars().collect::<Vec<_>>();
+            glob(&pattern, &name)
+        }
+
+        fn glob(pattern: &[char], name: &[char]) -> bool {
+            match pattern.split_first() {
+                None => name.is_empty(),
+                Some(('*', rest)) => (0..=name.len()).any(|skip| glob(rest, &name[skip..])),
+                Some((&expected, rest)) => match name.split_first() {
+                    Some((&actual, name)) if expected == '?' || expected == actual => {
+                        glob(rest, name)
+                    }
+                    _ => false,
+                },
+            }
+        }
+
+        #[cfg(test)]
+        mod tests {
+            use super::Ignore;
+
+            #[test]
+            fn patterns_match_whole_names_with_wildcards() {
+                let ignore = Ignore::new(["target", ".*", "*.lock", "v?.tmp"]);
+
+                for name in [
+                    "target",
+                    ".git",
+                    ".",
+                    "Cargo.lock",
+                    ".lock",
+                    "v1.tmp",
+                    "vé.tmp",
+                ] {
+                    assert!(ignore.is_ignored(name), "{}", name);
+                }
+                for name in [
+                    "targets",
+                    "my.target",
+                    "lock",
+                    "Cargo.locked",
+                    "v10.tmp",
+                    "v.tmp",
+                ] {
+                    assert!(!ignore.is_ignored(name), "{}", name);
+                }
+                assert!(!Ignore::default().is_ignored("target"));
+            }
         }
     }
 }
 
 fn main() {
-    use fs::{Component, File, Folder};
+    use std::{path::Path, sync::mpsc, thread};
 
-    let file1 = File::new("File 1");
-    let file2 = File::new("File 2");
-    let file3 = File::new("File 3");
+    use fs::{Cancel, Component, File, Folder, Ignore, Query, Search, Stats, Tree, TreeError};
+
+    let file1 = File::new("File 1", "A rose by any other name\nwould smell as sweet");
+    let file2 = File::new("File 2", "Roses are red,\nviolets are blue");
+    let file3 = File::new("File 3", "rose, rose, rose");
 
     let mut folder1 = Folder::new("Folder 1");
-    folder1.add(file1);
+    folder1.add(file1).unwrap();
 
     let mut folder2 = Folder::new("Folder 2");
-    folder2.add(file2);
-    folder2.add(file3);
-    folder2.add(folder1);
+    folder2.add(file2).unwrap();
+    folder2.add(file3).unwrap();
+    folder2.add(folder1).unwrap();
+
+    for found in folder2.search(&Query::literal("rose")) {
+        println!("{}", found);
+    }
+
+    print!("{}", Tree::of(&folder2));
+    let stats = Stats::of(&folder2, 2);
+    println!(
+        "{} bytes in {} files and {} folders, {} levels deep, largest {:?}",
+        stats.size, stats.files, stats.folders, stats.depth, stats.largest
+    );
+
+    let queries = [
+        ("Ignoring case", Query::ignore_case("rose")),
+        ("Whole words", Query::whole_word("rose")),
+        ("Fuzzy", Query::fuzzy("rbn")),
+        ("Regex", Query::regex(r"\b(red|blue)$").unwrap()),
+        ("In File 2", Query::ignore_case("rose").name("* 2")),
+    ];
+    for (title, query) in queries {
+        println!("{}:", title);
+        for found in folder2.search(&query) {
+            println!("  {}", found);
+        }
+    }
+
+    // Components are edited by their paths in the tree.
+    let edits: [Result<(), TreeError>; 8] = [
+        folder2.rename("Folder 1/File 1", "Sonnet"),
+        folder2.move_to("File 3", "Folder 1"),
+        folder2.rename("File 2", "Folder 1"),
+        folder2.rename("File 2", "Poems/File 2"),
+        folder2.move_to("Folder 1", "Folder 1/Sonnet"),
+        folder2.move_to("File 2", "Folder 1/Sonnet"),
+        folder2.remove("File 4").map(drop),
+        folder2.add(File::new("File 2", "")),
+    ];
+    for err in edits.into_iter().filter_map(Result::err) {
+        println!("Cannot edit the tree: {}", err);
+    }
+    if let Some(sonnet) = folder2.find("Folder 1/Sonnet") {
+        for found in sonnet.search(&Query::literal("sweet")) {
+            println!("{}", found);
+        }
+    }
+    print!("{}", Tree::of(&folder2));
+
+    // The same tree can come from disk, here this crate without the build
+    // output and hidden files.
+    let ignore = Ignore::new(["target", ".*", "*.lock"]);
+    match Folder::open(env!("CARGO_MANIFEST_DIR"), &ignore) {
+        Ok(folder) => {
+            print!("{}", Tree::of(&folder));
+            let query = Query::literal("impl Component for").extension("rs");
+            for found in folder.search(&query) {
+                println!("{}", found);
+            }
+        }
+        Err(err) => println!("Cannot load the crate: {}", err),
+    }
+
+    // Larger trees, like all the patterns, are searched in parallel.
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).parent().unwrap();
+    let patterns = match Folder::open(root, &ignore) {
+        Ok(folder) => folder,
+        Err(err) => return println!("Cannot load the patterns: {}", err),
+    };
+
+    let stats = Stats::of(&patterns, 3);
+    println!(
+        "Patterns: {} bytes in {} files and {} folders, {} levels deep",
+        stats.size, stats.files, stats.folders, stats.depth
+    );
+    for (path, size) in stats.largest.iter() {
+        println!("  {} ({} B)", path.display(), size);
+    }
+
+    let query = Query::whole_word("fn").extension("rs");
+    let sequential = patterns.search(&query);
+    let parallel = Search::new(query).threads(4).collect(&patterns);
+    println!(
+        "Found {} functions, in the same order in parallel: {}",
+        sequential.len(),
+        parallel == sequential
+    );
+
+    // Matches are streamed while the search goes on, until the limit.
+    let search = Search::new(Query::literal("Box<dyn")).limit(3);
+    let (sender, receiver) = mpsc::channel();
+    thread::scope(|scope| {
+        scope.spawn(|| search.stream(&patterns, sender));
+        for found in receiver {
+            println!("{}", found);
+        }
+    });
 
-    folder2.search("rose");
+    // A cancelled search starts nothing new.
+    let search = Search::new(Query::literal("fn "));
+    let cancel: Cancel = search.canceller();
+    cancel.cancel();
+    println!(
+        "Found after cancelling: {}",
+        search.collect(&patterns).len()
+    );
 }